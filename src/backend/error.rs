@@ -0,0 +1,229 @@
+use std::fmt;
+
+/// Why a leaderboard request failed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommError {
+    /// the request could not be sent or the response could not be read
+    Network(String),
+    /// no response within the request timeout
+    Timeout,
+    /// the backend is down or overloaded for now, a 5xx or 429 status
+    Unavailable(String),
+    /// the backend didn't accept our key
+    Auth(String),
+    /// an entry with this name already exists
+    DuplicateName,
+    /// the backend refused the submitted values
+    Validation(String),
+    /// any other error the backend answered with, all messages are kept
+    Server(Vec<String>),
+}
+
+impl CommError {
+    /// Whether sending the same request again might succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            CommError::Network(_) | CommError::Timeout | CommError::Unavailable(_)
+        )
+    }
+
+    /// Machine readable code, sent as the `code` extension of graphql errors
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommError::Network(_) => "NETWORK",
+            CommError::Timeout => "TIMEOUT",
+            CommError::Unavailable(_) => "UNAVAILABLE",
+            CommError::Auth(_) => "UNAUTHORIZED",
+            CommError::DuplicateName => "DUPLICATE_NAME",
+            CommError::Validation(_) => "VALIDATION",
+            CommError::Server(_) => "INTERNAL",
+        }
+    }
+
+    /// The raw reason without the advice `Display` adds
+    pub fn message(&self) -> String {
+        match self {
+            CommError::Network(e)
+            | CommError::Unavailable(e)
+            | CommError::Auth(e)
+            | CommError::Validation(e) => e.clone(),
+            CommError::Timeout => "timed out".to_string(),
+            CommError::DuplicateName => "Instance is not unique.".to_string(),
+            CommError::Server(messages) => messages.join("; "),
+        }
+    }
+
+    /// Turns the errors of a graphql response into one error. Uses the `code` extension the
+    /// self-hosted server sets, and fauna's messages otherwise.
+    pub fn from_graphql(errors: Vec<graphql_client::Error>) -> Self {
+        let code = errors.iter().find_map(|e| {
+            e.extensions
+                .as_ref()
+                .and_then(|extensions| extensions.get("code"))
+                .and_then(|code| code.as_str())
+        });
+        let mut messages: Vec<String> = errors.iter().map(|e| e.message.clone()).collect();
+        if messages.is_empty() {
+            messages.push("the leaderboard reported an error without saying why".to_string());
+        }
+        let first = messages[0].clone();
+
+        match code {
+            Some("UNAUTHORIZED") => return CommError::Auth(first),
+            Some("DUPLICATE_NAME") => return CommError::DuplicateName,
+            Some("VALIDATION") => return CommError::Validation(first),
+            _ => {}
+        }
+
+        let lower = first.to_lowercase();
+        if lower.contains("not unique") {
+            CommError::DuplicateName
+        } else if lower.contains("invalid database secret")
+            || lower.contains("unauthorized")
+            || lower.contains("authorization")
+        {
+            CommError::Auth(first)
+        } else {
+            CommError::Server(messages)
+        }
+    }
+
+    /// The error an http status stands for. Outages are transient so requests are retried or
+    /// queued.
+    pub fn from_status(status: reqwest::StatusCode) -> Option<Self> {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Some(CommError::Auth(status.to_string()))
+            }
+            status
+                if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                Some(CommError::Unavailable(status.to_string()))
+            }
+            status if !status.is_success() => Some(CommError::Server(vec![status.to_string()])),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CommError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommError::Network(e) => write!(
+                f,
+                "Could not reach the leaderboard ({}). Check your internet connection.",
+                e
+            ),
+            CommError::Timeout => write!(
+                f,
+                "The leaderboard took too long to answer. Try again in a moment."
+            ),
+            CommError::Unavailable(e) => write!(
+                f,
+                "The leaderboard is unavailable right now ({}). Try again in a moment.",
+                e
+            ),
+            CommError::Auth(_) => write!(
+                f,
+                "The leaderboard did not accept this game's key. Try updating the game."
+            ),
+            CommError::DuplicateName => write!(f, "That name is already taken, pick another one."),
+            CommError::Validation(e) => write!(f, "The entry was refused: {}", e),
+            CommError::Server(messages) => {
+                write!(
+                    f,
+                    "The leaderboard returned an error: {}",
+                    messages.join("; ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommError {}
+
+impl From<reqwest::Error> for CommError {
+    fn from(e: reqwest::Error) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if e.is_timeout() {
+            return CommError::Timeout;
+        }
+        CommError::Network(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graphql_error(message: &str, code: Option<&str>) -> graphql_client::Error {
+        graphql_client::Error {
+            message: message.to_string(),
+            locations: None,
+            path: None,
+            extensions: code.map(|code| {
+                let mut extensions = std::collections::HashMap::new();
+                extensions.insert("code".to_string(), serde_json::json!(code));
+                extensions
+            }),
+        }
+    }
+
+    #[test]
+    fn graphql_errors_are_classified() {
+        assert_eq!(
+            CommError::from_graphql(vec![graphql_error("x", Some("DUPLICATE_NAME"))]),
+            CommError::DuplicateName
+        );
+        assert_eq!(
+            CommError::from_graphql(vec![graphql_error("Instance is not unique.", None)]),
+            CommError::DuplicateName
+        );
+        assert_eq!(
+            CommError::from_graphql(vec![graphql_error("Invalid database secret.", None)]),
+            CommError::Auth("Invalid database secret.".to_string())
+        );
+        assert_eq!(
+            CommError::from_graphql(vec![graphql_error("too long", Some("VALIDATION"))]),
+            CommError::Validation("too long".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_errors_keep_a_message() {
+        let e = CommError::from_graphql(Vec::new());
+        assert!(matches!(&e, CommError::Server(messages) if !messages[0].is_empty()));
+        assert!(!e.message().is_empty());
+    }
+
+    #[test]
+    fn statuses_are_classified() {
+        assert_eq!(CommError::from_status(reqwest::StatusCode::OK), None);
+        assert!(matches!(
+            CommError::from_status(reqwest::StatusCode::UNAUTHORIZED),
+            Some(CommError::Auth(_))
+        ));
+        assert!(matches!(
+            CommError::from_status(reqwest::StatusCode::BAD_REQUEST),
+            Some(CommError::Server(_))
+        ));
+    }
+
+    #[test]
+    fn outages_are_transient() {
+        for status in [
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            reqwest::StatusCode::BAD_GATEWAY,
+            reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            reqwest::StatusCode::GATEWAY_TIMEOUT,
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+        ] {
+            let e = CommError::from_status(status).unwrap();
+            assert!(e.is_transient(), "{} is not transient", status);
+        }
+        assert!(!CommError::from_status(reqwest::StatusCode::BAD_REQUEST)
+            .unwrap()
+            .is_transient());
+    }
+}
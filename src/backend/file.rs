@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+use super::memory::{MemoryBackend, Store};
+use super::{CommError, Entry, EntryPatch, LeaderboardBackend, NewEntry, Page};
+
+/// Keeps entries in a json file, for offline or lan events.
+/// The whole file is rewritten after every change.
+pub struct FileBackend {
+    path: PathBuf,
+    inner: MemoryBackend,
+}
+
+impl FileBackend {
+    /// Opens the store at `path`, starting empty if the file does not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let store = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<Store>(&text)
+                .map_err(|e| format!("could not parse {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Store::default(),
+            Err(e) => return Err(format!("could not read {}: {}", path.display(), e)),
+        };
+        Ok(FileBackend {
+            path,
+            inner: MemoryBackend::from_store(store),
+        })
+    }
+
+    /// Writes `store` to the file. Called with the store locked, so a failed write leaves
+    /// memory unchanged and concurrent writes can't persist an older state.
+    fn save(&self, store: &Store) -> Result<(), CommError> {
+        let text =
+            serde_json::to_string(store).map_err(|e| CommError::Server(vec![e.to_string()]))?;
+        // write next to the target and rename so a crash can't leave a half written file
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, text)
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| {
+                CommError::Server(vec![format!(
+                    "could not write {}: {}",
+                    self.path.display(),
+                    e
+                )])
+            })
+    }
+}
+
+#[async_trait]
+impl LeaderboardBackend for FileBackend {
+    async fn submit(&self, entry: NewEntry) -> Result<Entry, CommError> {
+        self.inner
+            .transaction(|store| store.submit(entry), |store| self.save(store))
+    }
+
+    async fn list(
+        &self,
+        size: i64,
+        cursor: Option<String>,
+        challenge: Option<String>,
+    ) -> Result<Page, CommError> {
+        self.inner.list(size, cursor, challenge).await
+    }
+
+    async fn list_all(&self, size: i64, cursor: Option<String>) -> Result<Page, CommError> {
+        self.inner.list_all(size, cursor).await
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, id: &str, patch: EntryPatch) -> Result<Option<Entry>, CommError> {
+        self.inner
+            .transaction(|store| store.update(id, patch), |store| self.save(store))
+    }
+
+    async fn delete(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        self.inner
+            .transaction(|store| store.delete(id), |store| self.save(store))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future::block_on;
+
+    /// A path in a fresh directory under the system temp dir
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "unfair-advantage-file-backend-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("leaderboard.json")
+    }
+
+    fn new_entry(name: &str) -> NewEntry {
+        NewEntry {
+            name: name.to_string(),
+            score: Some(0.5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn changes_survive_a_reload() {
+        let path = temp_path("reload");
+        let backend = FileBackend::open(&path).unwrap();
+        let kept = block_on(backend.submit(new_entry("kept"))).unwrap();
+        let removed = block_on(backend.submit(new_entry("removed"))).unwrap();
+        block_on(backend.update(
+            &kept.id,
+            EntryPatch {
+                name: Some("renamed".to_string()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+        block_on(backend.delete(&removed.id)).unwrap();
+
+        let reloaded = FileBackend::open(&path).unwrap();
+        assert_eq!(
+            block_on(reloaded.list_all(10, None)).unwrap(),
+            block_on(backend.list_all(10, None)).unwrap()
+        );
+        assert_eq!(
+            block_on(reloaded.get(&kept.id)).unwrap().unwrap().name,
+            "renamed"
+        );
+        assert_eq!(block_on(reloaded.get(&removed.id)).unwrap(), None);
+        // ids keep counting up after a reload
+        let next = block_on(reloaded.submit(new_entry("next"))).unwrap();
+        assert_ne!(next.id, removed.id);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn failed_writes_leave_the_store_unchanged() {
+        let path = temp_path("failed")
+            .with_file_name("missing")
+            .join("leaderboard.json");
+        let backend = FileBackend::open(&path).unwrap();
+        assert!(block_on(backend.submit(new_entry("lost"))).is_err());
+        assert!(block_on(backend.list_all(10, None))
+            .unwrap()
+            .data
+            .is_empty());
+        std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+}
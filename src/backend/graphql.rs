@@ -0,0 +1,329 @@
+use async_trait::async_trait;
+use graphql_client::{GraphQLQuery, Response};
+
+use super::{CommError, Entry, EntryPatch, LeaderboardBackend, NewEntry, Page};
+
+const FAUNA_GRAPHQL_ENDPOINT: &str = "https://graphql.fauna.com/graphql";
+// the game is useless without the key, the server and tools only need it to talk to fauna
+#[cfg(feature = "game")]
+const FAUNA_API_TOKEN: Option<&str> = Some(env!("UNFAIR_ADVANTAGE_PUBLIC_FAUNA_CLIENT_KEY"));
+#[cfg(not(feature = "game"))]
+const FAUNA_API_TOKEN: Option<&str> = option_env!("UNFAIR_ADVANTAGE_PUBLIC_FAUNA_CLIENT_KEY");
+
+// fauna's `_ts` scalar
+type Long = i64;
+
+// graphql query to write name to database
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/create_entry.graphql"
+)]
+pub struct createDrawings;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/all_drawings.graphql"
+)]
+pub struct allDrawings;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/main_board.graphql"
+)]
+pub struct mainBoard;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/drawings_by_challenge.graphql"
+)]
+pub struct drawingsByChallenge;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/find_drawing.graphql"
+)]
+pub struct findDrawing;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/find_brush.graphql"
+)]
+pub struct findBrush;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_drawing.graphql"
+)]
+pub struct updateDrawing;
+
+#[allow(non_camel_case_types)] // must match name in graphql file
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/delete_drawing.graphql"
+)]
+pub struct deleteDrawing;
+
+// graphql_client generates a separate type per query, this copies the fields a query selected
+macro_rules! entry {
+    ($data:expr, $($field:ident),*) => {{
+        let data = $data;
+        // a query selecting every field leaves nothing to default
+        #[allow(clippy::needless_update)]
+        let entry = Entry {
+            $($field: data.$field,)*
+            ..Entry::default()
+        };
+        entry
+    }};
+}
+
+/// Talks to a graphql endpoint implementing `graphql/schema.graphql`, by default fauna.
+/// One client with the auth header is shared by all requests.
+pub struct GraphQlBackend {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl GraphQlBackend {
+    pub fn new(endpoint: impl Into<String>, token: impl AsRef<str>) -> Self {
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Ok(value) =
+            reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token.as_ref()))
+        {
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+
+        GraphQlBackend {
+            endpoint: endpoint.into(),
+            client: reqwest::Client::builder()
+                .default_headers(headers)
+                .build()
+                .expect("could not build http client"),
+        }
+    }
+
+    /// Fails if the fauna key wasn't set at build time, only possible without the `game` feature
+    pub fn fauna() -> Result<Self, String> {
+        let token = FAUNA_API_TOKEN.ok_or_else(|| {
+            "built without UNFAIR_ADVANTAGE_PUBLIC_FAUNA_CLIENT_KEY, fauna can't be used"
+                .to_string()
+        })?;
+        Ok(Self::new(FAUNA_GRAPHQL_ENDPOINT, token))
+    }
+
+    async fn post<Q: GraphQLQuery>(
+        &self,
+        variables: Q::Variables,
+    ) -> Result<Q::ResponseData, CommError> {
+        let response = self
+            .client
+            .post(self.endpoint.as_str())
+            .json(&Q::build_query(variables))
+            .send()
+            .await?;
+        // a rejected key or an unhealthy proxy may not answer with a graphql response, and an
+        // outage has to stay retryable whatever the body says
+        let status = response.status();
+        if let Some(e) = CommError::from_status(status) {
+            if matches!(e, CommError::Auth(_)) || e.is_transient() {
+                return Err(e);
+            }
+        }
+        let res: Response<Q::ResponseData> = match response.json().await {
+            Ok(res) => res,
+            Err(e) => return Err(CommError::from_status(status).unwrap_or_else(|| e.into())),
+        };
+
+        if let Some(errors) = res.errors {
+            return Err(CommError::from_graphql(errors));
+        }
+
+        res.data
+            .ok_or_else(|| CommError::Server(vec!["response contained no data".to_string()]))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl LeaderboardBackend for GraphQlBackend {
+    async fn submit(&self, entry: NewEntry) -> Result<Entry, CommError> {
+        let variables = create_drawings::Variables {
+            new_drawing: create_drawings::DrawingsInput {
+                name: entry.name,
+                score: entry.score,
+                brush: entry.brush,
+                shape: entry.shape,
+                drawing: entry.drawing,
+                seed: entry.seed,
+                replay: entry.replay,
+                verified: entry.verified,
+                challenge: entry.challenge,
+                level: entry.level,
+                mode: entry.mode,
+                version: entry.version,
+                duration: entry.duration,
+                hidden: entry.hidden,
+                // graphql_client keeps the schema's names for input fields
+                remainingTime: entry.remaining_time,
+            },
+        };
+        let data = self.post::<createDrawings>(variables).await?;
+        Ok(entry!(data.create_drawings, id, ts, name, score, verified))
+    }
+
+    async fn list(
+        &self,
+        size: i64,
+        cursor: Option<String>,
+        challenge: Option<String>,
+    ) -> Result<Page, CommError> {
+        if let Some(challenge) = challenge {
+            let variables = drawings_by_challenge::Variables {
+                challenge,
+                size,
+                cursor,
+            };
+            let page = self
+                .post::<drawingsByChallenge>(variables)
+                .await?
+                .drawings_by_challenge;
+            return Ok(Page {
+                data: page
+                    .data
+                    .into_iter()
+                    .flatten()
+                    .map(|d| {
+                        entry!(
+                            d, id, ts, name, score, seed, verified, challenge, level, mode, hidden
+                        )
+                    })
+                    .collect(),
+                after: page.after,
+                before: page.before,
+            });
+        }
+
+        // fauna can't index missing fields, see graphql/fauna/indexes.fql for how this works
+        let page = self
+            .post::<mainBoard>(main_board::Variables { size, cursor })
+            .await?
+            .main_board;
+        Ok(Page {
+            data: page
+                .data
+                .into_iter()
+                .flatten()
+                .map(|d| {
+                    entry!(d, id, ts, name, score, seed, verified, challenge, level, mode, hidden)
+                })
+                .collect(),
+            after: page.after,
+            before: page.before,
+        })
+    }
+
+    async fn list_all(&self, size: i64, cursor: Option<String>) -> Result<Page, CommError> {
+        let page = self
+            .post::<allDrawings>(all_drawings::Variables { size, cursor })
+            .await?
+            .all_drawings;
+        Ok(Page {
+            data: page
+                .data
+                .into_iter()
+                .flatten()
+                .map(|d| {
+                    entry!(d, id, ts, name, score, seed, verified, challenge, level, mode, hidden)
+                })
+                .collect(),
+            after: page.after,
+            before: page.before,
+        })
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        let data = self
+            .post::<findDrawing>(find_drawing::Variables { id: id.to_string() })
+            .await?;
+        Ok(data.find_drawings_by_id.map(|d| {
+            entry!(
+                d,
+                id,
+                ts,
+                name,
+                score,
+                brush,
+                shape,
+                drawing,
+                seed,
+                replay,
+                verified,
+                challenge,
+                level,
+                mode,
+                version,
+                duration,
+                hidden,
+                remaining_time
+            )
+        }))
+    }
+
+    async fn brush(&self, id: &str) -> Result<Option<String>, CommError> {
+        let data = self
+            .post::<findBrush>(find_brush::Variables { id: id.to_string() })
+            .await?;
+        Ok(data.find_drawings_by_id.and_then(|d| d.brush))
+    }
+
+    async fn update(&self, id: &str, patch: EntryPatch) -> Result<Option<Entry>, CommError> {
+        let variables = update_drawing::Variables {
+            id: id.to_string(),
+            patch: update_drawing::PartialUpdateDrawingsInput {
+                name: patch.name,
+                score: patch.score,
+                brush: patch.brush,
+                shape: patch.shape,
+                drawing: patch.drawing,
+                seed: patch.seed,
+                replay: patch.replay,
+                verified: patch.verified,
+                challenge: patch.challenge,
+                level: patch.level,
+                mode: patch.mode,
+                version: patch.version,
+                duration: patch.duration,
+                hidden: patch.hidden,
+                remainingTime: patch.remaining_time,
+            },
+        };
+        let data = self.post::<updateDrawing>(variables).await?;
+        Ok(data
+            .partial_update_drawings
+            .map(|d| entry!(d, id, ts, name, score, verified, challenge, hidden)))
+    }
+
+    async fn delete(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        let data = self
+            .post::<deleteDrawing>(delete_drawing::Variables { id: id.to_string() })
+            .await?;
+        Ok(data
+            .delete_drawings
+            .map(|d| entry!(d, id, ts, name, score, verified, challenge, hidden)))
+    }
+}
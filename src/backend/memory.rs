@@ -0,0 +1,304 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use super::{now_micros, CommError, Entry, EntryPatch, LeaderboardBackend, NewEntry, Page};
+
+/// Keeps entries in memory. Useful for tests and as the store behind [`super::FileBackend`].
+#[derive(Default)]
+pub struct MemoryBackend {
+    store: Mutex<Store>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Store {
+    next_id: u64,
+    entries: Vec<Entry>,
+}
+
+impl MemoryBackend {
+    pub(crate) fn from_store(store: Store) -> Self {
+        MemoryBackend {
+            store: Mutex::new(store),
+        }
+    }
+
+    /// Applies `change` to a copy of the store and keeps it only if `commit` accepts the
+    /// changed store. The lock is held throughout, so commits happen in the order of the
+    /// changes.
+    pub(crate) fn transaction<T>(
+        &self,
+        change: impl FnOnce(&mut Store) -> Result<T, CommError>,
+        commit: impl FnOnce(&Store) -> Result<(), CommError>,
+    ) -> Result<T, CommError> {
+        let mut store = self.store.lock().unwrap();
+        let mut changed = store.clone();
+        let result = change(&mut changed)?;
+        commit(&changed)?;
+        *store = changed;
+        Ok(result)
+    }
+}
+
+impl Store {
+    pub(crate) fn submit(&mut self, entry: NewEntry) -> Result<Entry, CommError> {
+        // names are unique per board
+        if self
+            .entries
+            .iter()
+            .any(|e| e.name == entry.name && e.challenge == entry.challenge)
+        {
+            return Err(CommError::DuplicateName);
+        }
+        self.next_id += 1;
+        let new = Entry {
+            id: self.next_id.to_string(),
+            ts: now_micros(),
+            name: entry.name,
+            score: entry.score,
+            brush: entry.brush,
+            shape: entry.shape,
+            drawing: entry.drawing,
+            seed: entry.seed,
+            replay: entry.replay,
+            verified: entry.verified,
+            challenge: entry.challenge,
+            level: entry.level,
+            mode: entry.mode,
+            version: entry.version,
+            duration: entry.duration,
+            hidden: entry.hidden,
+            remaining_time: entry.remaining_time,
+        };
+        self.entries.push(new.clone());
+        Ok(new)
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        id: &str,
+        patch: EntryPatch,
+    ) -> Result<Option<Entry>, CommError> {
+        let (name, challenge) = match self.entries.iter().find(|e| e.id == id) {
+            Some(entry) => (
+                patch.name.clone().unwrap_or_else(|| entry.name.clone()),
+                patch.challenge.clone().or_else(|| entry.challenge.clone()),
+            ),
+            None => return Ok(None),
+        };
+        if self
+            .entries
+            .iter()
+            .any(|e| e.name == name && e.challenge == challenge && e.id != id)
+        {
+            return Err(CommError::DuplicateName);
+        }
+        let entry = self.entries.iter_mut().find(|e| e.id == id).unwrap();
+        if let Some(name) = patch.name {
+            entry.name = name;
+        }
+        if patch.score.is_some() {
+            entry.score = patch.score;
+        }
+        if patch.brush.is_some() {
+            entry.brush = patch.brush;
+        }
+        if patch.shape.is_some() {
+            entry.shape = patch.shape;
+        }
+        if patch.drawing.is_some() {
+            entry.drawing = patch.drawing;
+        }
+        if patch.seed.is_some() {
+            entry.seed = patch.seed;
+        }
+        if patch.replay.is_some() {
+            entry.replay = patch.replay;
+        }
+        if patch.verified.is_some() {
+            entry.verified = patch.verified;
+        }
+        if patch.challenge.is_some() {
+            entry.challenge = patch.challenge;
+        }
+        if patch.level.is_some() {
+            entry.level = patch.level;
+        }
+        if patch.mode.is_some() {
+            entry.mode = patch.mode;
+        }
+        if patch.version.is_some() {
+            entry.version = patch.version;
+        }
+        if patch.duration.is_some() {
+            entry.duration = patch.duration;
+        }
+        if patch.hidden.is_some() {
+            entry.hidden = patch.hidden;
+        }
+        if patch.remaining_time.is_some() {
+            entry.remaining_time = patch.remaining_time;
+        }
+        entry.ts = now_micros();
+        Ok(Some(entry.clone()))
+    }
+
+    pub(crate) fn delete(&mut self, id: &str) -> Result<Option<Entry>, CommError> {
+        Ok(self
+            .entries
+            .iter()
+            .position(|e| e.id == id)
+            .map(|index| self.entries.remove(index)))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl LeaderboardBackend for MemoryBackend {
+    async fn submit(&self, entry: NewEntry) -> Result<Entry, CommError> {
+        self.store.lock().unwrap().submit(entry)
+    }
+
+    async fn list(
+        &self,
+        size: i64,
+        cursor: Option<String>,
+        challenge: Option<String>,
+    ) -> Result<Page, CommError> {
+        let store = self.store.lock().unwrap();
+        let board: Vec<&Entry> = store
+            .entries
+            .iter()
+            .filter(|e| e.challenge == challenge)
+            .collect();
+        page(board, size, cursor)
+    }
+
+    async fn list_all(&self, size: i64, cursor: Option<String>) -> Result<Page, CommError> {
+        let store = self.store.lock().unwrap();
+        page(store.entries.iter().collect(), size, cursor)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        let store = self.store.lock().unwrap();
+        Ok(store.entries.iter().find(|e| e.id == id).cloned())
+    }
+
+    async fn update(&self, id: &str, patch: EntryPatch) -> Result<Option<Entry>, CommError> {
+        self.store.lock().unwrap().update(id, patch)
+    }
+
+    async fn delete(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        self.store.lock().unwrap().delete(id)
+    }
+}
+
+/// `size` entries of `board` from `cursor` on, cursors are plain indices
+fn page(board: Vec<&Entry>, size: i64, cursor: Option<String>) -> Result<Page, CommError> {
+    let start = match cursor {
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| CommError::Validation(format!("invalid cursor '{}'", cursor)))?,
+        None => 0,
+    };
+    let size = size.max(1) as usize;
+    let end = (start + size).min(board.len());
+    let data = board
+        .get(start..end)
+        .unwrap_or_default()
+        .iter()
+        .map(|e| (*e).clone())
+        .collect();
+    Ok(Page {
+        data,
+        after: (end < board.len()).then(|| end.to_string()),
+        before: (start > 0).then(|| start.saturating_sub(size).to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future::block_on;
+
+    fn new_entry(name: &str, challenge: Option<&str>) -> NewEntry {
+        NewEntry {
+            name: name.to_string(),
+            challenge: challenge.map(str::to_string),
+            ..NewEntry::default()
+        }
+    }
+
+    #[test]
+    fn pages_follow_the_cursors() {
+        let backend = MemoryBackend::default();
+        for i in 0..5 {
+            block_on(backend.submit(new_entry(&format!("player {}", i), None))).unwrap();
+        }
+
+        let first = block_on(backend.list(2, None, None)).unwrap();
+        assert_eq!(first.data.len(), 2);
+        assert_eq!(first.before, None);
+        let second = block_on(backend.list(2, first.after.clone(), None)).unwrap();
+        assert_eq!(second.data[0].name, "player 2");
+        assert_eq!(second.before, Some("0".to_string()));
+        let last = block_on(backend.list(2, second.after, None)).unwrap();
+        assert_eq!(last.data.len(), 1);
+        assert_eq!(last.after, None);
+
+        assert!(block_on(backend.list(2, Some("x".to_string()), None)).is_err());
+    }
+
+    #[test]
+    fn boards_are_listed_separately() {
+        let backend = MemoryBackend::default();
+        block_on(backend.submit(new_entry("main", None))).unwrap();
+        block_on(backend.submit(new_entry("daily", Some("daily-2022-02-20")))).unwrap();
+
+        let main = block_on(backend.list(10, None, None)).unwrap();
+        assert_eq!(main.data.len(), 1);
+        assert_eq!(main.data[0].name, "main");
+        let daily = block_on(backend.list(10, None, Some("daily-2022-02-20".to_string()))).unwrap();
+        assert_eq!(daily.data.len(), 1);
+        assert_eq!(daily.data[0].name, "daily");
+        let all = block_on(backend.list_all(10, None)).unwrap();
+        assert_eq!(all.data.len(), 2);
+    }
+
+    #[test]
+    fn names_are_unique_per_board() {
+        let backend = MemoryBackend::default();
+        let first = block_on(backend.submit(new_entry("ana", None))).unwrap();
+        assert_eq!(
+            block_on(backend.submit(new_entry("ana", None))),
+            Err(CommError::DuplicateName)
+        );
+        block_on(backend.submit(new_entry("ana", Some("timed")))).unwrap();
+
+        let other = block_on(backend.submit(new_entry("bo", None))).unwrap();
+        let rename = EntryPatch {
+            name: Some("ana".to_string()),
+            ..EntryPatch::default()
+        };
+        assert_eq!(
+            block_on(backend.update(&other.id, rename.clone())),
+            Err(CommError::DuplicateName)
+        );
+        // renaming an entry to its own name is fine
+        assert!(block_on(backend.update(&first.id, rename))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn missing_entries_are_none() {
+        let backend = MemoryBackend::default();
+        let entry = block_on(backend.submit(new_entry("ana", None))).unwrap();
+        assert_eq!(
+            block_on(backend.delete(&entry.id)).unwrap(),
+            Some(entry.clone())
+        );
+        assert_eq!(block_on(backend.get(&entry.id)).unwrap(), None);
+        assert_eq!(block_on(backend.delete(&entry.id)).unwrap(), None);
+    }
+}
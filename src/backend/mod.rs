@@ -0,0 +1,217 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, sync::Arc};
+
+mod error;
+#[cfg(not(target_arch = "wasm32"))]
+mod file;
+mod graphql;
+mod memory;
+mod verify;
+
+pub use error::CommError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use file::FileBackend;
+pub use graphql::GraphQlBackend;
+pub use memory::MemoryBackend;
+pub use verify::{MismatchPolicy, VerifyingBackend};
+
+/// A stored leaderboard entry. Mirrors the `Drawings` type of the graphql schema.
+/// Fields a query doesn't select are left at their defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    /// document timestamp in microseconds since the unix epoch
+    pub ts: i64,
+    pub name: String,
+    pub score: Option<f64>,
+    pub brush: Option<String>,
+    pub shape: Option<String>,
+    pub drawing: Option<String>,
+    /// brush seed, see [`crate::scoring::BrushShape::from_seed`]
+    pub seed: Option<i64>,
+    /// json encoded [`crate::scoring::Replay`]
+    pub replay: Option<String>,
+    /// set by a verifying server once the replay reproduced the score
+    pub verified: Option<bool>,
+    /// daily challenge the entry competes in, `None` for the main board
+    pub challenge: Option<String>,
+    /// id of the level, see [`crate::scoring::LEVELS`]. Older entries have none and were
+    /// painted on the first level.
+    pub level: Option<String>,
+    /// game mode the run was played in, `free`, `daily` or `timed`
+    pub mode: Option<String>,
+    /// version of the game that submitted the entry
+    pub version: Option<String>,
+    /// seconds spent painting
+    pub duration: Option<f64>,
+    /// hidden by a moderator, the game leaves it off the leaderboard
+    pub hidden: Option<bool>,
+    /// seconds left on the clock when a timed run ended
+    pub remaining_time: Option<f64>,
+}
+
+impl Entry {
+    /// Whether the entry takes a rank on the leaderboard. Entries hidden by a moderator stay
+    /// stored but are left off the board, so are entries a verifying server stored without
+    /// being able to confirm their score.
+    pub fn is_ranked(&self) -> bool {
+        self.hidden != Some(true) && self.verified != Some(false)
+    }
+}
+
+/// Higher score first. Equal scores are ranked by who got there first, so ranks don't change
+/// between refreshes.
+pub fn rank_order(a: &Entry, b: &Entry) -> Ordering {
+    let a_score = a.score.unwrap_or(f64::NEG_INFINITY);
+    let b_score = b.score.unwrap_or(f64::NEG_INFINITY);
+    b_score
+        .partial_cmp(&a_score)
+        .unwrap_or(Ordering::Equal)
+        .then(a.ts.cmp(&b.ts))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// Values for a new entry. Mirrors `DrawingsInput`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewEntry {
+    pub name: String,
+    pub score: Option<f64>,
+    pub brush: Option<String>,
+    pub shape: Option<String>,
+    pub drawing: Option<String>,
+    pub seed: Option<i64>,
+    pub replay: Option<String>,
+    pub verified: Option<bool>,
+    pub challenge: Option<String>,
+    pub level: Option<String>,
+    pub mode: Option<String>,
+    pub version: Option<String>,
+    pub duration: Option<f64>,
+    pub hidden: Option<bool>,
+    pub remaining_time: Option<f64>,
+}
+
+impl From<Entry> for NewEntry {
+    /// The values of a stored entry, e.g. to copy it into another backend
+    fn from(entry: Entry) -> Self {
+        NewEntry {
+            name: entry.name,
+            score: entry.score,
+            brush: entry.brush,
+            shape: entry.shape,
+            drawing: entry.drawing,
+            seed: entry.seed,
+            replay: entry.replay,
+            verified: entry.verified,
+            challenge: entry.challenge,
+            level: entry.level,
+            mode: entry.mode,
+            version: entry.version,
+            duration: entry.duration,
+            hidden: entry.hidden,
+            remaining_time: entry.remaining_time,
+        }
+    }
+}
+
+/// Fields to change on an existing entry, `None` leaves the field as is.
+/// Mirrors `PartialUpdateDrawingsInput`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntryPatch {
+    pub name: Option<String>,
+    pub score: Option<f64>,
+    pub brush: Option<String>,
+    pub shape: Option<String>,
+    pub drawing: Option<String>,
+    pub seed: Option<i64>,
+    pub replay: Option<String>,
+    pub verified: Option<bool>,
+    pub challenge: Option<String>,
+    pub level: Option<String>,
+    pub mode: Option<String>,
+    pub version: Option<String>,
+    pub duration: Option<f64>,
+    pub hidden: Option<bool>,
+    pub remaining_time: Option<f64>,
+}
+
+/// One page of entries with the cursors to the neighbouring pages.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    pub data: Vec<Entry>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+}
+
+/// Storage for leaderboard entries. The game sends all its requests through one of these.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait LeaderboardBackend: Send + Sync {
+    async fn submit(&self, entry: NewEntry) -> Result<Entry, CommError>;
+    /// Entries of the board of `challenge`, or of the main board for `None`.
+    /// Entries in a page may leave out the large fields, `get` returns the whole entry
+    async fn list(
+        &self,
+        size: i64,
+        cursor: Option<String>,
+        challenge: Option<String>,
+    ) -> Result<Page, CommError>;
+    /// Entries of every board, e.g. for backups
+    async fn list_all(&self, size: i64, cursor: Option<String>) -> Result<Page, CommError>;
+    async fn get(&self, id: &str) -> Result<Option<Entry>, CommError>;
+    /// Only the brush of an entry, `None` if the entry or its brush doesn't exist
+    async fn brush(&self, id: &str) -> Result<Option<String>, CommError> {
+        Ok(self.get(id).await?.and_then(|entry| entry.brush))
+    }
+    async fn update(&self, id: &str, patch: EntryPatch) -> Result<Option<Entry>, CommError>;
+    async fn delete(&self, id: &str) -> Result<Option<Entry>, CommError>;
+}
+
+/// Environment variable used to pick the backend, see [`from_env`]
+pub const BACKEND_ENV: &str = "UNFAIR_ADVANTAGE_LEADERBOARD";
+/// Bearer token sent to a self-hosted graphql backend
+pub const TOKEN_ENV: &str = "UNFAIR_ADVANTAGE_LEADERBOARD_TOKEN";
+
+/// Picks the backend from `UNFAIR_ADVANTAGE_LEADERBOARD`, see [`from_spec`]. Unset uses fauna.
+pub fn from_env() -> Result<Arc<dyn LeaderboardBackend>, String> {
+    match std::env::var(BACKEND_ENV) {
+        Err(_) => Ok(Arc::new(GraphQlBackend::fauna()?)),
+        Ok(value) => from_spec(&value),
+    }
+}
+
+/// `fauna`, an `http(s)://` url of a self-hosted server, `memory` to keep entries in memory
+/// or `file:<path>` to use a json file.
+pub fn from_spec(spec: &str) -> Result<Arc<dyn LeaderboardBackend>, String> {
+    if spec == "fauna" {
+        return Ok(Arc::new(GraphQlBackend::fauna()?));
+    }
+    if spec.starts_with("http://") || spec.starts_with("https://") {
+        let token = std::env::var(TOKEN_ENV).unwrap_or_default();
+        return Ok(Arc::new(GraphQlBackend::new(spec, token)));
+    }
+    if spec == "memory" {
+        return Ok(Arc::new(MemoryBackend::default()));
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = spec.strip_prefix("file:") {
+        return Ok(Arc::new(FileBackend::open(path)?));
+    }
+    Err(format!("unknown leaderboard backend '{}'", spec))
+}
+
+/// current time in microseconds since the unix epoch, the unit fauna uses for `_ts`
+pub fn now_micros() -> i64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_micros() as i64)
+            .unwrap_or_default()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        (js_sys::Date::now() * 1000.0) as i64
+    }
+}
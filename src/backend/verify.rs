@@ -0,0 +1,349 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::{CommError, Entry, EntryPatch, LeaderboardBackend, NewEntry, Page};
+use crate::daily::DailyChallenge;
+use crate::nickname::NicknameRules;
+use crate::scoring::{
+    self, BrushShape, Level, Replay, ScoreBreakdown, CANVAS_HEIGHT, CANVAS_WIDTH, LEVELS, MAX_SEED,
+    TIMED_BOARD,
+};
+/// The client and the server run the same code, so any real difference is a forged score
+const SCORE_TOLERANCE: f64 = 1e-6;
+/// Painting time and remaining time of a timed run add up to its limit, give or take rounding
+const TIME_TOLERANCE: f64 = 0.5;
+
+/// What to do with a submission whose claimed score doesn't match its replay
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MismatchPolicy {
+    Reject,
+    /// store it with the replayed score and `verified: false`
+    Flag,
+}
+
+/// Re-simulates submitted replays and stores the server computed score, brush and shape.
+/// Nicknames of new and renamed entries are sanitized, everything else is passed through to the
+/// inner backend.
+pub struct VerifyingBackend {
+    inner: Arc<dyn LeaderboardBackend>,
+    rules: Arc<Rules>,
+}
+
+/// Everything a submission is checked against, shared with the blocking replay task
+struct Rules {
+    /// rgba bytes of the target image
+    target: Vec<u8>,
+    require_replay: bool,
+    on_mismatch: MismatchPolicy,
+    nicknames: NicknameRules,
+}
+
+impl VerifyingBackend {
+    pub fn new(
+        inner: Arc<dyn LeaderboardBackend>,
+        target: Vec<u8>,
+        require_replay: bool,
+        on_mismatch: MismatchPolicy,
+    ) -> Result<Self, String> {
+        if target.len() != CANVAS_WIDTH * CANVAS_HEIGHT * 4 {
+            return Err(format!(
+                "target image must be {}x{} rgba",
+                CANVAS_WIDTH, CANVAS_HEIGHT
+            ));
+        }
+        Ok(VerifyingBackend {
+            inner,
+            rules: Arc::new(Rules {
+                target,
+                require_replay,
+                on_mismatch,
+                nicknames: NicknameRules::default(),
+            }),
+        })
+    }
+
+    /// Replaces the default nickname blocklist
+    pub fn with_nickname_rules(mut self, nicknames: NicknameRules) -> Self {
+        if let Some(rules) = Arc::get_mut(&mut self.rules) {
+            rules.nicknames = nicknames;
+        }
+        self
+    }
+}
+
+impl Rules {
+    fn verify(&self, entry: &mut NewEntry) -> Result<(), String> {
+        entry.name = self
+            .nicknames
+            .sanitize(&entry.name)
+            .map_err(|e| e.to_string())?;
+        if let Some(level) = &entry.level {
+            Level::from_id(level).ok_or_else(|| format!("unknown level '{}'", level))?;
+        }
+        if entry.challenge.as_deref() == Some(TIMED_BOARD) {
+            verify_timed(entry)?;
+        } else if let Some(challenge) = &entry.challenge {
+            let daily = DailyChallenge::from_id(challenge)
+                .ok_or_else(|| format!("unknown challenge '{}'", challenge))?;
+            if entry.seed != Some(daily.seed as i64) {
+                return Err("seed does not match the challenge".to_string());
+            }
+        }
+
+        let (seed, replay) = match (entry.seed, entry.replay.as_deref()) {
+            (Some(seed), Some(replay)) => (seed, replay),
+            _ if self.require_replay => {
+                return Err("submission needs a seed and a replay".to_string())
+            }
+            _ => {
+                entry.verified = Some(false);
+                return Ok(());
+            }
+        };
+
+        let seed = u32::try_from(seed)
+            .ok()
+            .filter(|seed| *seed <= MAX_SEED)
+            .ok_or_else(|| "invalid seed".to_string())?;
+        let replay: Replay =
+            serde_json::from_str(replay).map_err(|e| format!("invalid replay: {}", e))?;
+        // checked before any painting, a replay is only as long as the run it claims to be
+        if replay.0.len() > Replay::max_points(entry.duration) {
+            return Err("replay is longer than the run".to_string());
+        }
+        let replay = replay.distinct();
+
+        let shape = BrushShape::from_seed(seed);
+        let canvas = replay.simulate(&shape);
+        let score = ScoreBreakdown::calculate(&self.target, &canvas).score();
+        let matches = entry
+            .score
+            .is_some_and(|claimed| (claimed - score).abs() < SCORE_TOLERANCE);
+        if !matches && self.on_mismatch == MismatchPolicy::Reject {
+            return Err(format!(
+                "claimed score {:.1} does not match the replayed score {:.1}",
+                entry.score.unwrap_or_default(),
+                score
+            ));
+        }
+
+        entry.score = Some(score);
+        entry.verified = Some(matches);
+        entry.brush = Some(base64::encode(scoring::render_brush(&shape)));
+        entry.shape = Some(serde_json::to_string(&shape).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+}
+
+/// Timed runs must have ended within their level's time limit
+fn verify_timed(entry: &NewEntry) -> Result<(), String> {
+    if entry.mode.as_deref() != Some("timed") {
+        return Err("only timed runs go on the timed board".to_string());
+    }
+    let level = match &entry.level {
+        Some(level) => Level::from_id(level).ok_or_else(|| format!("unknown level '{}'", level))?,
+        None => &LEVELS[0],
+    };
+    let limit = level.time_limit as f64;
+    let remaining = entry
+        .remaining_time
+        .ok_or_else(|| "timed runs need the remaining time".to_string())?;
+    if !(0.0..=limit).contains(&remaining) {
+        return Err("remaining time is out of range".to_string());
+    }
+    if let Some(duration) = entry.duration {
+        if (duration + remaining - limit).abs() > TIME_TOLERANCE {
+            return Err("painting time does not match the remaining time".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Replaying takes long enough to stall other requests, so it runs on tokio's blocking threads
+#[cfg(not(target_arch = "wasm32"))]
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, CommError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| CommError::Server(vec![format!("verification failed: {}", e)]))
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn run_blocking<T>(f: impl FnOnce() -> T) -> Result<T, CommError> {
+    Ok(f())
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl LeaderboardBackend for VerifyingBackend {
+    async fn submit(&self, mut entry: NewEntry) -> Result<Entry, CommError> {
+        let rules = self.rules.clone();
+        let entry = run_blocking(move || {
+            rules.verify(&mut entry)?;
+            Ok(entry)
+        })
+        .await?
+        .map_err(CommError::Validation)?;
+        self.inner.submit(entry).await
+    }
+
+    async fn list(
+        &self,
+        size: i64,
+        cursor: Option<String>,
+        challenge: Option<String>,
+    ) -> Result<Page, CommError> {
+        self.inner.list(size, cursor, challenge).await
+    }
+
+    async fn list_all(&self, size: i64, cursor: Option<String>) -> Result<Page, CommError> {
+        self.inner.list_all(size, cursor).await
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, id: &str, mut patch: EntryPatch) -> Result<Option<Entry>, CommError> {
+        if let Some(name) = &patch.name {
+            let name = self
+                .rules
+                .nicknames
+                .sanitize(name)
+                .map_err(|e| CommError::Validation(e.to_string()))?;
+            patch.name = Some(name);
+        }
+        self.inner.update(id, patch).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Option<Entry>, CommError> {
+        self.inner.delete(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::scoring::{blank_canvas, paint_brush};
+
+    /// A target with a dark square in the middle of the canvas
+    fn target() -> Vec<u8> {
+        let mut target = [255u8; 4].repeat(CANVAS_WIDTH * CANVAS_HEIGHT);
+        for y in 200..400 {
+            for x in 200..400 {
+                let i = (y * CANVAS_WIDTH + x) * 4;
+                target[i..i + 4].copy_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+        target
+    }
+
+    fn rules(require_replay: bool, on_mismatch: MismatchPolicy) -> Rules {
+        Rules {
+            target: target(),
+            require_replay,
+            on_mismatch,
+            nicknames: NicknameRules::default(),
+        }
+    }
+
+    /// Paints like the game does, a position per frame with the brush held still in between,
+    /// and returns the entry the game would send
+    fn played_run(seed: u32) -> NewEntry {
+        let shape = BrushShape::from_seed(seed);
+        let mut canvas = blank_canvas();
+        let mut replay = Replay::default();
+        let path = (0..200)
+            .map(|i| [-150.0 + i as f32 * 1.5, (i as f32 / 10.0).sin() * 80.0])
+            .chain(std::iter::repeat_n([150.0, 0.0], 20))
+            .chain((0..100).map(|i| [100.0 - i as f32 * 2.0, -60.0]));
+        for position in path {
+            paint_brush(&mut canvas, &shape, position);
+            replay.record(position);
+        }
+        NewEntry {
+            name: "painter".to_string(),
+            score: Some(ScoreBreakdown::calculate(&target(), &canvas).score()),
+            seed: Some(seed as i64),
+            replay: Some(serde_json::to_string(&replay).unwrap()),
+            ..NewEntry::default()
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_the_client_score() {
+        for seed in [0, 1, 42, MAX_SEED] {
+            let mut entry = played_run(seed);
+            let claimed = entry.score;
+            rules(true, MismatchPolicy::Reject)
+                .verify(&mut entry)
+                .unwrap();
+            assert_eq!(entry.score, claimed);
+            assert_eq!(entry.verified, Some(true));
+        }
+    }
+
+    #[test]
+    fn forged_scores_are_caught() {
+        let mut entry = played_run(7);
+        entry.score = Some(100.0);
+        assert!(rules(true, MismatchPolicy::Reject)
+            .verify(&mut entry.clone())
+            .is_err());
+
+        let replayed = played_run(7).score;
+        rules(true, MismatchPolicy::Flag)
+            .verify(&mut entry)
+            .unwrap();
+        assert_eq!(entry.score, replayed);
+        assert_eq!(entry.verified, Some(false));
+    }
+
+    #[test]
+    fn replays_longer_than_the_run_are_rejected() {
+        let mut entry = played_run(7);
+        entry.duration = Some(0.5);
+        assert!(rules(true, MismatchPolicy::Reject)
+            .verify(&mut entry.clone())
+            .is_err());
+
+        entry.duration = Some(10.0);
+        rules(true, MismatchPolicy::Reject)
+            .verify(&mut entry)
+            .unwrap();
+        assert_eq!(entry.verified, Some(true));
+    }
+
+    #[test]
+    fn submissions_need_a_replay_unless_allowed() {
+        let mut entry = played_run(7);
+        entry.replay = None;
+        assert!(rules(true, MismatchPolicy::Reject)
+            .verify(&mut entry.clone())
+            .is_err());
+        rules(false, MismatchPolicy::Reject)
+            .verify(&mut entry)
+            .unwrap();
+        assert_eq!(entry.verified, Some(false));
+    }
+
+    #[test]
+    fn submit_stores_the_replayed_score() {
+        let backend = VerifyingBackend::new(
+            Arc::new(MemoryBackend::default()),
+            target(),
+            true,
+            MismatchPolicy::Reject,
+        )
+        .unwrap();
+        let entry = played_run(3);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let stored = runtime.block_on(backend.submit(entry.clone())).unwrap();
+        assert_eq!(stored.score, entry.score);
+        assert_eq!(stored.verified, Some(true));
+    }
+}
@@ -0,0 +1,598 @@
+//! Command line tool for leaderboard backups, event archives, seeding a self-hosted server and
+//! moderation.
+//!
+//! The backend is picked like in the game, see `UNFAIR_ADVANTAGE_LEADERBOARD`.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use unfair_coloring_competition::{
+    backend::{
+        self, now_micros, rank_order, CommError, Entry, EntryPatch, LeaderboardBackend, NewEntry,
+    },
+    scoring::BRUSH_IMAGE_SIZE,
+};
+
+/// Entries fetched per list request
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Parser)]
+#[clap(about = "Export, import and moderate Unfair Coloring Competition leaderboards")]
+struct Args {
+    /// `fauna`, the url of a self-hosted server, `memory` or `file:<path>`
+    #[clap(
+        long,
+        env = "UNFAIR_ADVANTAGE_LEADERBOARD",
+        default_value = "fauna",
+        value_parser
+    )]
+    backend: String,
+    /// Every rename, hide and delete is appended to this file as a json line
+    #[clap(long, default_value = "leaderboard-audit.jsonl", value_parser)]
+    audit_log: PathBuf,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write all entries of a board to a file or stdout. Json exports hold the whole entries
+    /// and can be imported again, csv leaves out brush, drawing and replay.
+    Export {
+        #[clap(long, value_enum, value_parser, default_value = "json")]
+        format: Format,
+        /// Output file, stdout when not given
+        #[clap(long, value_parser)]
+        out: Option<PathBuf>,
+        /// Export the board of a challenge, e.g. `daily-2022-02-20` or `timed`
+        #[clap(long, value_parser)]
+        challenge: Option<String>,
+        /// Export the entries of every board, e.g. for a backup
+        #[clap(long, action, conflicts_with = "challenge")]
+        all_boards: bool,
+        /// Write the brush of every entry to `<dir>/<id>.png`
+        #[clap(long, value_parser)]
+        brushes: Option<PathBuf>,
+    },
+    /// Submit the entries of a json export. The backend assigns new ids and timestamps.
+    Import {
+        /// File written by `export --format json`
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+    /// Print every board with its number of entries
+    Boards,
+    /// Print the board in rank order, with hidden and unverified entries listed unranked
+    List {
+        /// List the board of a daily challenge
+        #[clap(long, value_parser)]
+        challenge: Option<String>,
+    },
+    /// Change the nickname of an entry
+    Rename {
+        #[clap(value_parser)]
+        id: String,
+        #[clap(value_parser)]
+        name: String,
+    },
+    /// Leave an entry off the leaderboard without deleting it
+    Hide {
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Show a hidden entry again
+    Unhide {
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Delete an entry for good
+    Delete {
+        #[clap(value_parser)]
+        id: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Format {
+    Json,
+    Csv,
+}
+
+/// One line of the audit log
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    /// microseconds since the unix epoch
+    ts: i64,
+    backend: &'a str,
+    action: &'a str,
+    id: &'a str,
+    /// the entry before the change
+    name: &'a str,
+    score: Option<f64>,
+    challenge: Option<&'a str>,
+    new_name: Option<&'a str>,
+}
+
+fn exit_with(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Entries to fetch
+enum Scope {
+    /// the board of a challenge, the main board for `None`
+    Board(Option<String>),
+    Everything,
+}
+
+/// Every entry in `scope`, following the `after` cursors
+async fn fetch_all(
+    backend: &dyn LeaderboardBackend,
+    scope: &Scope,
+) -> Result<Vec<Entry>, CommError> {
+    let mut entries = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = match scope {
+            Scope::Board(challenge) => backend.list(PAGE_SIZE, cursor, challenge.clone()).await?,
+            Scope::Everything => backend.list_all(PAGE_SIZE, cursor).await?,
+        };
+        entries.extend(page.data);
+        match page.after {
+            Some(after) => cursor = Some(after),
+            None => return Ok(entries),
+        }
+    }
+}
+
+fn to_csv(entries: &[Entry]) -> String {
+    let mut csv = String::from(
+        "id,ts,name,score,verified,challenge,level,mode,version,duration,seed,hidden,remaining_time\n",
+    );
+    for entry in entries {
+        let fields = [
+            entry.id.clone(),
+            entry.ts.to_string(),
+            entry.name.clone(),
+            optional(entry.score),
+            optional(entry.verified),
+            optional(entry.challenge.as_ref()),
+            optional(entry.level.as_ref()),
+            optional(entry.mode.as_ref()),
+            optional(entry.version.as_ref()),
+            optional(entry.duration),
+            optional(entry.seed),
+            optional(entry.hidden),
+            optional(entry.remaining_time),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Quotes fields containing separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the brush as png, returns false when the entry has no valid brush
+fn write_brush(entry: &Entry, dir: &Path) -> Result<bool, String> {
+    let rgba = match entry.brush.as_deref().and_then(|b| base64::decode(b).ok()) {
+        Some(rgba) => rgba,
+        None => return Ok(false),
+    };
+    let size = BRUSH_IMAGE_SIZE as u32;
+    let image = match image::RgbaImage::from_raw(size, size, rgba) {
+        Some(image) => image,
+        None => return Ok(false),
+    };
+    // ids are numbers on every backend, but don't trust them as file names
+    let file_name: String = entry
+        .id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    let path = dir.join(format!("{}.png", file_name));
+    image
+        .save(&path)
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+async fn export(
+    backend: &dyn LeaderboardBackend,
+    format: Format,
+    out: Option<PathBuf>,
+    scope: Scope,
+    brushes: Option<PathBuf>,
+) {
+    let mut entries = fetch_all(backend, &scope)
+        .await
+        .unwrap_or_else(|e| exit_with(format!("could not list entries: {}", e)));
+
+    // pages leave out the large fields, which a json export must keep to be imported again
+    if matches!(format, Format::Json) || brushes.is_some() {
+        for entry in &mut entries {
+            match backend.get(&entry.id).await {
+                Ok(Some(full)) => *entry = full,
+                // deleted since it was listed, keep what the page had
+                Ok(None) => {}
+                Err(e) => exit_with(format!("could not fetch entry {}: {}", entry.id, e)),
+            }
+        }
+    }
+
+    if let Some(dir) = &brushes {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|e| exit_with(format!("could not create {}: {}", dir.display(), e)));
+        let mut written = 0;
+        for entry in &entries {
+            if write_brush(entry, dir).unwrap_or_else(|e| exit_with(e)) {
+                written += 1;
+            }
+        }
+        eprintln!("wrote {} brushes to {}", written, dir.display());
+    }
+
+    let text = match format {
+        Format::Json => serde_json::to_string_pretty(&entries).unwrap(),
+        Format::Csv => to_csv(&entries),
+    };
+    match &out {
+        Some(path) => std::fs::write(path, text)
+            .unwrap_or_else(|e| exit_with(format!("could not write {}: {}", path.display(), e))),
+        None => std::io::stdout()
+            .write_all(text.as_bytes())
+            .unwrap_or_else(|e| exit_with(e)),
+    }
+    eprintln!("exported {} entries", entries.len());
+}
+
+async fn import(backend: &dyn LeaderboardBackend, file: PathBuf) {
+    let text = std::fs::read_to_string(&file)
+        .unwrap_or_else(|e| exit_with(format!("could not read {}: {}", file.display(), e)));
+    let entries: Vec<Entry> = serde_json::from_str(&text)
+        .unwrap_or_else(|e| exit_with(format!("could not parse {}: {}", file.display(), e)));
+
+    let total = entries.len();
+    let mut failed = 0;
+    for entry in entries {
+        let name = entry.name.clone();
+        if let Err(e) = backend.submit(NewEntry::from(entry)).await {
+            eprintln!("skipped '{}': {}", name, e);
+            failed += 1;
+        }
+    }
+    eprintln!("imported {} of {} entries", total - failed, total);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn boards(entries: &[Entry]) {
+    let mut counts = std::collections::BTreeMap::new();
+    for entry in entries {
+        let board = entry.challenge.as_deref().unwrap_or("main");
+        *counts.entry(board).or_insert(0) += 1;
+    }
+    println!("{:<24}  {:>7}", "board", "entries");
+    for (board, count) in counts {
+        println!("{:<24}  {:>7}", board, count);
+    }
+}
+
+/// The board in rank order as a table. Entries the game leaves off the board are listed
+/// without a rank.
+fn list(entries: &mut [Entry]) -> String {
+    entries.sort_by(rank_order);
+    let mut table = format!(
+        "{:>5}  {:<20}  {:>7}  {:<24}  flags\n",
+        "rank", "id", "score", "name"
+    );
+    let mut rank = 0;
+    for entry in entries.iter() {
+        let rank = if entry.is_ranked() {
+            rank += 1;
+            rank.to_string()
+        } else {
+            "-".to_string()
+        };
+        let mut flags = Vec::new();
+        if entry.hidden == Some(true) {
+            flags.push("hidden");
+        }
+        if entry.verified == Some(false) {
+            flags.push("unverified");
+        }
+        table += &format!(
+            "{:>5}  {:<20}  {:>7.1}  {:<24}  {}\n",
+            rank,
+            entry.id,
+            entry.score.unwrap_or_default(),
+            entry.name,
+            flags.join(",")
+        );
+    }
+    table
+}
+
+/// Applies a moderation action and records it in the audit log. Returns the entry as it was
+/// before the change.
+async fn moderate(
+    backend: &dyn LeaderboardBackend,
+    backend_spec: &str,
+    audit_log: &Path,
+    action: &str,
+    id: &str,
+    patch: Option<EntryPatch>,
+) -> Result<Entry, String> {
+    let before = match backend.get(id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Err(format!("no entry with id {}", id)),
+        Err(e) => return Err(format!("could not fetch entry {}: {}", id, e)),
+    };
+    let new_name = patch.as_ref().and_then(|patch| patch.name.clone());
+    let result = match patch {
+        Some(patch) => backend.update(id, patch).await,
+        None => backend.delete(id).await,
+    };
+    match result {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(format!("entry {} was deleted in the meantime", id)),
+        Err(e) => return Err(format!("could not {} entry {}: {}", action, id, e)),
+    }
+
+    let record = AuditRecord {
+        ts: now_micros(),
+        backend: backend_spec,
+        action,
+        id,
+        name: &before.name,
+        score: before.score,
+        challenge: before.challenge.as_deref(),
+        new_name: new_name.as_deref(),
+    };
+    let line = serde_json::to_string(&record).unwrap();
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_log)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| {
+            format!(
+                "{} done, but could not write the audit log {}: {}",
+                action,
+                audit_log.display(),
+                e
+            )
+        })?;
+    Ok(before)
+}
+
+/// Runs a moderation action for the command line, exiting when it fails
+async fn report(
+    backend: &dyn LeaderboardBackend,
+    args: &Args,
+    action: &str,
+    id: &str,
+    patch: Option<EntryPatch>,
+) {
+    let before = moderate(backend, &args.backend, &args.audit_log, action, id, patch)
+        .await
+        .unwrap_or_else(|e| exit_with(e));
+    eprintln!("{} '{}' ({})", action, before.name, id);
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let backend: Arc<dyn LeaderboardBackend> =
+        backend::from_spec(&args.backend).unwrap_or_else(|e| exit_with(e));
+
+    match &args.command {
+        Command::Export {
+            format,
+            out,
+            challenge,
+            all_boards,
+            brushes,
+        } => {
+            let scope = if *all_boards {
+                Scope::Everything
+            } else {
+                Scope::Board(challenge.clone())
+            };
+            export(&*backend, *format, out.clone(), scope, brushes.clone()).await
+        }
+        Command::Import { file } => import(&*backend, file.clone()).await,
+        Command::Boards => {
+            let entries = fetch_all(&*backend, &Scope::Everything)
+                .await
+                .unwrap_or_else(|e| exit_with(format!("could not list entries: {}", e)));
+            boards(&entries);
+        }
+        Command::List { challenge } => {
+            let mut entries = fetch_all(&*backend, &Scope::Board(challenge.clone()))
+                .await
+                .unwrap_or_else(|e| exit_with(format!("could not list entries: {}", e)));
+            print!("{}", list(&mut entries));
+        }
+        Command::Rename { id, name } => {
+            let patch = EntryPatch {
+                name: Some(name.clone()),
+                ..EntryPatch::default()
+            };
+            report(&*backend, &args, "rename", id, Some(patch)).await
+        }
+        Command::Hide { id } => {
+            let patch = EntryPatch {
+                hidden: Some(true),
+                ..EntryPatch::default()
+            };
+            report(&*backend, &args, "hide", id, Some(patch)).await
+        }
+        Command::Unhide { id } => {
+            let patch = EntryPatch {
+                hidden: Some(false),
+                ..EntryPatch::default()
+            };
+            report(&*backend, &args, "unhide", id, Some(patch)).await
+        }
+        Command::Delete { id } => report(&*backend, &args, "delete", id, None).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unfair_coloring_competition::backend::MemoryBackend;
+
+    fn entry(id: &str, score: f64, hidden: Option<bool>, verified: Option<bool>) -> Entry {
+        Entry {
+            id: id.to_string(),
+            name: format!("player {}", id),
+            score: Some(score),
+            hidden,
+            verified,
+            ..Entry::default()
+        }
+    }
+
+    fn audit_log(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "unfair-advantage-audit-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn list_ranks_only_entries_the_game_shows() {
+        let mut entries = vec![
+            entry("low", 10.0, None, Some(true)),
+            entry("hidden", 90.0, Some(true), None),
+            entry("unverified", 80.0, None, Some(false)),
+            entry("high", 50.0, None, None),
+        ];
+        let table = list(&mut entries);
+        let rows: Vec<Vec<&str>> = table
+            .lines()
+            .skip(1)
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        let ranks: Vec<(&str, &str)> = rows.iter().map(|row| (row[0], row[1])).collect();
+        assert_eq!(
+            ranks,
+            vec![
+                ("-", "hidden"),
+                ("-", "unverified"),
+                ("1", "high"),
+                ("2", "low")
+            ]
+        );
+        assert!(rows[0].contains(&"hidden"));
+        assert!(rows[1].contains(&"unverified"));
+    }
+
+    #[tokio::test]
+    async fn moderation_changes_entries_and_is_audited() {
+        let backend = MemoryBackend::default();
+        let mut ids = Vec::new();
+        for name in ["first", "second", "third"] {
+            let new = NewEntry {
+                name: name.to_string(),
+                score: Some(1.0),
+                ..NewEntry::default()
+            };
+            ids.push(backend.submit(new).await.unwrap().id);
+        }
+        let log = audit_log("moderation");
+
+        let rename = EntryPatch {
+            name: Some("renamed".to_string()),
+            ..EntryPatch::default()
+        };
+        let hide = EntryPatch {
+            hidden: Some(true),
+            ..EntryPatch::default()
+        };
+        moderate(&backend, "memory", &log, "rename", &ids[0], Some(rename))
+            .await
+            .unwrap();
+        moderate(&backend, "memory", &log, "hide", &ids[1], Some(hide))
+            .await
+            .unwrap();
+        moderate(&backend, "memory", &log, "delete", &ids[2], None)
+            .await
+            .unwrap();
+
+        assert_eq!(backend.get(&ids[0]).await.unwrap().unwrap().name, "renamed");
+        assert_eq!(
+            backend.get(&ids[1]).await.unwrap().unwrap().hidden,
+            Some(true)
+        );
+        assert_eq!(backend.get(&ids[2]).await.unwrap(), None);
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|record| {
+                (
+                    record["action"].as_str().unwrap(),
+                    record["id"].as_str().unwrap(),
+                    record["name"].as_str().unwrap(),
+                    record["new_name"].as_str(),
+                    record["backend"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "rename",
+                    ids[0].as_str(),
+                    "first",
+                    Some("renamed"),
+                    "memory"
+                ),
+                ("hide", ids[1].as_str(), "second", None, "memory"),
+                ("delete", ids[2].as_str(), "third", None, "memory"),
+            ]
+        );
+        std::fs::remove_file(&log).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_actions_are_not_audited() {
+        let backend = MemoryBackend::default();
+        let log = audit_log("failed");
+        assert!(
+            moderate(&backend, "memory", &log, "delete", "missing", None)
+                .await
+                .is_err()
+        );
+        assert!(!log.exists());
+    }
+}
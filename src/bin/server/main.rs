@@ -0,0 +1,122 @@
+//! Self-hostable leaderboard server implementing `graphql/schema.graphql`.
+//!
+//! The game can be pointed at it with `UNFAIR_ADVANTAGE_LEADERBOARD=http://<addr>/graphql`.
+
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{extract::ContentLengthLimit, http::HeaderMap, routing::post, Extension, Router};
+use clap::Parser;
+use std::net::SocketAddr;
+use std::{path::PathBuf, sync::Arc};
+use tower_http::cors::CorsLayer;
+use unfair_coloring_competition::{
+    backend::{self, MismatchPolicy, VerifyingBackend},
+    nickname::NicknameRules,
+    scoring::TARGET_IMAGE,
+};
+
+mod schema;
+
+use schema::{LeaderboardSchema, Token};
+
+/// Largest request accepted, enough for an entry with its brush, replay and stored canvas
+const MAX_BODY_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Parser)]
+#[clap(about = "Self-hosted leaderboard for Unfair Coloring Competition")]
+struct Args {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1:8000", value_parser)]
+    listen: SocketAddr,
+    /// Where entries are stored, `file:<path>` or `memory`
+    #[clap(long, default_value = "file:leaderboard.json", value_parser)]
+    store: String,
+    /// Bearer token required for update and delete
+    #[clap(long, env = "UNFAIR_ADVANTAGE_ADMIN_KEY", value_parser)]
+    admin_key: Option<String>,
+    /// Run without --admin-key, letting anyone update and delete entries
+    #[clap(long, action)]
+    insecure_no_admin: bool,
+    /// The game's asset folder, used to load the target image for replays
+    #[clap(long, default_value = "assets", value_parser)]
+    assets: PathBuf,
+    /// Store submissions without a seed and replay, e.g. from older clients, with
+    /// `verified: false` instead of rejecting them. Their score can't be checked.
+    #[clap(long, action)]
+    allow_no_replay: bool,
+    /// Store submissions whose score doesn't match their replay as unverified instead of
+    /// rejecting them
+    #[clap(long, action)]
+    flag_mismatches: bool,
+    /// Nickname blocklist, one word per line, replacing the built in one
+    #[clap(long, value_parser)]
+    blocklist: Option<PathBuf>,
+}
+
+fn exit_with(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+async fn graphql_handler(
+    schema: Extension<LeaderboardSchema>,
+    headers: HeaderMap,
+    ContentLengthLimit(req): ContentLengthLimit<GraphQLRequest, MAX_BODY_BYTES>,
+) -> GraphQLResponse {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    schema
+        .execute(req.into_inner().data(Token(token)))
+        .await
+        .into()
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    if args.admin_key.is_none() && !args.insecure_no_admin {
+        exit_with("--admin-key is required, pass --insecure-no-admin to leave update and delete open to everyone");
+    }
+
+    if args.store.starts_with("http") {
+        exit_with("--store must be a local store, not another graphql server");
+    }
+    let store = backend::from_spec(&args.store).unwrap_or_else(|e| exit_with(e));
+
+    let target_path = args.assets.join(TARGET_IMAGE);
+    let target = image::open(&target_path)
+        .unwrap_or_else(|e| exit_with(format!("could not load {}: {}", target_path.display(), e)))
+        .to_rgba8()
+        .into_raw();
+    let on_mismatch = if args.flag_mismatches {
+        MismatchPolicy::Flag
+    } else {
+        MismatchPolicy::Reject
+    };
+    let nicknames = match &args.blocklist {
+        Some(path) => std::fs::read_to_string(path)
+            .map(|text| NicknameRules::with_blocklist(&text))
+            .unwrap_or_else(|e| exit_with(format!("could not read {}: {}", path.display(), e))),
+        None => NicknameRules::default(),
+    };
+    let backend = VerifyingBackend::new(store, target, !args.allow_no_replay, on_mismatch)
+        .unwrap_or_else(|e| exit_with(e))
+        .with_nickname_rules(nicknames);
+    if args.admin_key.is_none() {
+        eprintln!("warning: --insecure-no-admin given, update and delete are open to everyone");
+    }
+
+    let schema = schema::build(Arc::new(backend), args.admin_key);
+    let app = Router::new()
+        .route("/graphql", post(graphql_handler))
+        .layer(Extension(schema))
+        .layer(CorsLayer::permissive());
+
+    println!("leaderboard listening on http://{}/graphql", args.listen);
+    axum::Server::bind(&args.listen)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
@@ -0,0 +1,442 @@
+use async_graphql::{
+    Context, EmptySubscription, ErrorExtensions, InputObject, InputValueError, InputValueResult,
+    Object, Scalar, ScalarType, Schema, SimpleObject, Value, ID,
+};
+use std::sync::Arc;
+use unfair_coloring_competition::backend::{
+    CommError, Entry, EntryPatch, LeaderboardBackend, NewEntry, Page,
+};
+
+pub type LeaderboardSchema = Schema<Query, Mutation, EmptySubscription>;
+
+/// Page size fauna uses when `_size` is not given
+const DEFAULT_PAGE_SIZE: i64 = 64;
+
+pub fn build(backend: Arc<dyn LeaderboardBackend>, admin_key: Option<String>) -> LeaderboardSchema {
+    Schema::build(Query, Mutation, EmptySubscription)
+        .data(backend)
+        .data(AdminKey(admin_key))
+        .finish()
+}
+
+struct AdminKey(Option<String>);
+
+/// Bearer token sent with a request, compared against the admin key for update and delete
+pub struct Token(pub Option<String>);
+
+fn require_admin(ctx: &Context<'_>) -> async_graphql::Result<()> {
+    let token = ctx.data_opt::<Token>().and_then(|t| t.0.as_deref());
+    match &ctx.data::<AdminKey>()?.0 {
+        None => Ok(()),
+        Some(key) if token.is_some_and(|token| same_key(token, key)) => Ok(()),
+        Some(_) => Err(to_graphql_error(CommError::Auth(
+            "admin key required".to_string(),
+        ))),
+    }
+}
+
+/// Compares every byte, so the time taken doesn't tell how much of a guessed key was right
+fn same_key(token: &str, key: &str) -> bool {
+    token.len() == key.len()
+        && token
+            .bytes()
+            .zip(key.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Keeps the error kind in the `code` extension so clients can tell errors apart
+fn to_graphql_error(e: CommError) -> async_graphql::Error {
+    async_graphql::Error::new(e.message())
+        .extend_with(|_, extensions| extensions.set("code", e.code()))
+}
+
+fn backend<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Arc<dyn LeaderboardBackend>> {
+    ctx.data::<Arc<dyn LeaderboardBackend>>()
+}
+
+/// fauna's 64 bit integer scalar, used for `_ts`
+pub struct Long(i64);
+
+#[Scalar]
+impl ScalarType for Long {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match value {
+            Value::Number(n) if n.is_i64() => Ok(Long(n.as_i64().unwrap())),
+            other => Err(InputValueError::expected_type(other)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Number(self.0.into())
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Drawings")]
+struct Drawings {
+    name: String,
+    #[graphql(name = "_id")]
+    id: ID,
+    score: Option<f64>,
+    shape: Option<String>,
+    brush: Option<String>,
+    drawing: Option<String>,
+    seed: Option<i64>,
+    replay: Option<String>,
+    verified: Option<bool>,
+    challenge: Option<String>,
+    level: Option<String>,
+    mode: Option<String>,
+    version: Option<String>,
+    duration: Option<f64>,
+    hidden: Option<bool>,
+    remaining_time: Option<f64>,
+    #[graphql(name = "_ts")]
+    ts: Long,
+}
+
+impl From<Entry> for Drawings {
+    fn from(entry: Entry) -> Self {
+        Drawings {
+            name: entry.name,
+            id: ID(entry.id),
+            score: entry.score,
+            shape: entry.shape,
+            brush: entry.brush,
+            drawing: entry.drawing,
+            seed: entry.seed,
+            replay: entry.replay,
+            verified: entry.verified,
+            challenge: entry.challenge,
+            level: entry.level,
+            mode: entry.mode,
+            version: entry.version,
+            duration: entry.duration,
+            hidden: entry.hidden,
+            remaining_time: entry.remaining_time,
+            ts: Long(entry.ts),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "DrawingsPage")]
+struct DrawingsPage {
+    data: Vec<Option<Drawings>>,
+    after: Option<String>,
+    before: Option<String>,
+}
+
+#[derive(InputObject)]
+#[graphql(name = "DrawingsInput")]
+struct DrawingsInput {
+    name: String,
+    score: Option<f64>,
+    brush: Option<String>,
+    shape: Option<String>,
+    drawing: Option<String>,
+    seed: Option<i64>,
+    replay: Option<String>,
+    verified: Option<bool>,
+    challenge: Option<String>,
+    level: Option<String>,
+    mode: Option<String>,
+    version: Option<String>,
+    duration: Option<f64>,
+    hidden: Option<bool>,
+    remaining_time: Option<f64>,
+}
+
+impl From<DrawingsInput> for NewEntry {
+    fn from(input: DrawingsInput) -> Self {
+        NewEntry {
+            name: input.name,
+            score: input.score,
+            brush: input.brush,
+            shape: input.shape,
+            drawing: input.drawing,
+            seed: input.seed,
+            replay: input.replay,
+            verified: input.verified,
+            challenge: input.challenge,
+            level: input.level,
+            mode: input.mode,
+            version: input.version,
+            duration: input.duration,
+            hidden: input.hidden,
+            remaining_time: input.remaining_time,
+        }
+    }
+}
+
+#[derive(InputObject)]
+#[graphql(name = "PartialUpdateDrawingsInput")]
+struct PartialUpdateDrawingsInput {
+    name: Option<String>,
+    score: Option<f64>,
+    brush: Option<String>,
+    shape: Option<String>,
+    drawing: Option<String>,
+    seed: Option<i64>,
+    replay: Option<String>,
+    verified: Option<bool>,
+    challenge: Option<String>,
+    level: Option<String>,
+    mode: Option<String>,
+    version: Option<String>,
+    duration: Option<f64>,
+    hidden: Option<bool>,
+    remaining_time: Option<f64>,
+}
+
+impl From<PartialUpdateDrawingsInput> for EntryPatch {
+    fn from(input: PartialUpdateDrawingsInput) -> Self {
+        EntryPatch {
+            name: input.name,
+            score: input.score,
+            brush: input.brush,
+            shape: input.shape,
+            drawing: input.drawing,
+            seed: input.seed,
+            replay: input.replay,
+            verified: input.verified,
+            challenge: input.challenge,
+            level: input.level,
+            mode: input.mode,
+            version: input.version,
+            duration: input.duration,
+            hidden: input.hidden,
+            remaining_time: input.remaining_time,
+        }
+    }
+}
+
+impl From<Page> for DrawingsPage {
+    fn from(page: Page) -> Self {
+        DrawingsPage {
+            data: page.data.into_iter().map(|e| Some(e.into())).collect(),
+            after: page.after,
+            before: page.before,
+        }
+    }
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Find a document from the collection of 'Drawings' by its id.
+    #[graphql(name = "findDrawingsByID")]
+    async fn find_drawings_by_id(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Option<Drawings>> {
+        Ok(backend(ctx)?
+            .get(&id)
+            .await
+            .map_err(to_graphql_error)?
+            .map(Drawings::from))
+    }
+
+    /// Entries of every board
+    async fn all_drawings(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "_size")] size: Option<i32>,
+        #[graphql(name = "_cursor")] cursor: Option<String>,
+    ) -> async_graphql::Result<DrawingsPage> {
+        let size = size.map(i64::from).unwrap_or(DEFAULT_PAGE_SIZE);
+        let page = backend(ctx)?
+            .list_all(size, cursor)
+            .await
+            .map_err(to_graphql_error)?;
+        Ok(page.into())
+    }
+
+    /// Entries without a challenge
+    async fn main_board(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "_size")] size: Option<i32>,
+        #[graphql(name = "_cursor")] cursor: Option<String>,
+    ) -> async_graphql::Result<DrawingsPage> {
+        let size = size.map(i64::from).unwrap_or(DEFAULT_PAGE_SIZE);
+        let page = backend(ctx)?
+            .list(size, cursor, None)
+            .await
+            .map_err(to_graphql_error)?;
+        Ok(page.into())
+    }
+
+    async fn drawings_by_challenge(
+        &self,
+        ctx: &Context<'_>,
+        challenge: String,
+        #[graphql(name = "_size")] size: Option<i32>,
+        #[graphql(name = "_cursor")] cursor: Option<String>,
+    ) -> async_graphql::Result<DrawingsPage> {
+        let size = size.map(i64::from).unwrap_or(DEFAULT_PAGE_SIZE);
+        let page = backend(ctx)?
+            .list(size, cursor, Some(challenge))
+            .await
+            .map_err(to_graphql_error)?;
+        Ok(page.into())
+    }
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    /// Create a new document in the collection of 'Drawings'
+    async fn create_drawings(
+        &self,
+        ctx: &Context<'_>,
+        data: DrawingsInput,
+    ) -> async_graphql::Result<Drawings> {
+        Ok(backend(ctx)?
+            .submit(data.into())
+            .await
+            .map_err(to_graphql_error)?
+            .into())
+    }
+
+    /// Update an existing document in the collection of 'Drawings'
+    async fn update_drawings(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        data: DrawingsInput,
+    ) -> async_graphql::Result<Option<Drawings>> {
+        require_admin(ctx)?;
+        let patch = EntryPatch {
+            name: Some(data.name),
+            score: data.score,
+            brush: data.brush,
+            shape: data.shape,
+            drawing: data.drawing,
+            seed: data.seed,
+            replay: data.replay,
+            verified: data.verified,
+            challenge: data.challenge,
+            level: data.level,
+            mode: data.mode,
+            version: data.version,
+            duration: data.duration,
+            hidden: data.hidden,
+            remaining_time: data.remaining_time,
+        };
+        Ok(backend(ctx)?
+            .update(&id, patch)
+            .await
+            .map_err(to_graphql_error)?
+            .map(Drawings::from))
+    }
+
+    /// Delete an existing document in the collection of 'Drawings'
+    async fn delete_drawings(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Option<Drawings>> {
+        require_admin(ctx)?;
+        Ok(backend(ctx)?
+            .delete(&id)
+            .await
+            .map_err(to_graphql_error)?
+            .map(Drawings::from))
+    }
+
+    /// Partially updates an existing document in the collection of 'Drawings'.
+    async fn partial_update_drawings(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        data: PartialUpdateDrawingsInput,
+    ) -> async_graphql::Result<Option<Drawings>> {
+        require_admin(ctx)?;
+        Ok(backend(ctx)?
+            .update(&id, data.into())
+            .await
+            .map_err(to_graphql_error)?
+            .map(Drawings::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future::block_on;
+    use serde_json::json;
+    use unfair_coloring_competition::backend::MemoryBackend;
+
+    /// Variables for the documents in `graphql/`, which are the queries the game sends
+    fn variables(file: &str, id: &str) -> serde_json::Value {
+        match file {
+            "all_drawings.graphql" | "main_board.graphql" => json!({ "size": 10 }),
+            "drawings_by_challenge.graphql" => json!({ "challenge": "timed", "size": 10 }),
+            "create_entry.graphql" => json!({
+                "new_drawing": { "name": "painter", "score": 50.0, "remainingTime": 12.5 }
+            }),
+            "find_brush.graphql" | "find_drawing.graphql" | "delete_drawing.graphql" => {
+                json!({ "id": id })
+            }
+            "update_drawing.graphql" => json!({ "id": id, "patch": { "hidden": true } }),
+            other => panic!("no variables for graphql/{}, add them to this test", other),
+        }
+    }
+
+    #[test]
+    fn the_games_queries_run_against_the_schema() {
+        let backend: Arc<dyn LeaderboardBackend> = Arc::new(MemoryBackend::default());
+        let stored = block_on(backend.submit(NewEntry {
+            name: "stored".to_string(),
+            score: Some(10.0),
+            challenge: Some("timed".to_string()),
+            ..NewEntry::default()
+        }))
+        .unwrap();
+        let schema = build(backend, None);
+
+        let mut files: Vec<String> = std::fs::read_dir("graphql")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".graphql") && name != "schema.graphql")
+            .collect();
+        // the others need the stored entry
+        files.sort_by_key(|name| (name == "delete_drawing.graphql", name.clone()));
+        for file in files {
+            let query = std::fs::read_to_string(format!("graphql/{}", file)).unwrap();
+            let request = async_graphql::Request::new(query).variables(
+                async_graphql::Variables::from_json(variables(&file, &stored.id)),
+            );
+            let response = block_on(schema.execute(request));
+            assert!(
+                response.errors.is_empty(),
+                "{}: {:?}",
+                file,
+                response.errors
+            );
+            let data = response.data.into_json().unwrap();
+            assert!(
+                data.as_object()
+                    .unwrap()
+                    .values()
+                    .all(|value| !value.is_null()),
+                "{} returned {}",
+                file,
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn keys_must_match_exactly() {
+        assert!(same_key("secret", "secret"));
+        assert!(!same_key("secreT", "secret"));
+        assert!(!same_key("secret", "secret2"));
+        assert!(!same_key("", "secret"));
+    }
+}
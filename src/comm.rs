@@ -1,14 +1,36 @@
 use async_channel::{Receiver, Sender};
-use bevy::prelude::{App, Commands, Plugin};
-use graphql_client::{reqwest::post_graphql, GraphQLQuery};
-use std::future::Future;
+use bevy::{
+    prelude::{error, info, warn, App, Commands, CoreStage, Plugin, Res, ResMut, Time},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+use std::{future::Future, sync::Arc, time::Duration};
 
-use crate::comm::create_drawings::DrawingsInput;
+use crate::{my_runs::RunHistory, storage};
+use unfair_coloring_competition::backend::{
+    self, CommError, Entry, GraphQlBackend, LeaderboardBackend, NewEntry, Page,
+};
+
+/// How long a single request may take before it fails with [`CommError::Timeout`]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// Attempts for idempotent requests, with the delay doubling after each failure
+const MAX_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Storage key of the submissions waiting to be sent
+const QUEUE_KEY: &str = "submission_queue";
+/// Storage key of the queued submissions the leaderboard refused
+const DROPPED_KEY: &str = "dropped_submissions";
+/// Seconds between attempts to send queued submissions while offline
+const QUEUE_RETRY_INTERVAL: f64 = 30.0;
 
 pub struct CommPlugin;
 impl Plugin for CommPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup_comm);
+        app.add_startup_system(setup_comm)
+            .insert_resource(SubmissionQueue::load())
+            .add_system_to_stage(CoreStage::PreUpdate, receive_responses)
+            .add_system(send_queued_submissions);
     }
 }
 
@@ -52,134 +74,490 @@ where
     });
 }
 
-pub struct CommChannels {
-    pub result_req_tx: Sender<DrawingsInput>,
-    pub result_res_rx: Receiver<Result<create_drawings::CreateDrawingsCreateDrawings, String>>,
-    pub all_drawings_req_tx: Sender<()>,
-    pub all_drawings_res_rx: Receiver<Result<all_drawings::AllDrawingsAllDrawings, String>>,
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
 }
 
-fn setup_comm(mut commands: Commands) {
-    let (result_req_tx, result_req_rx) = async_channel::bounded(1);
-    let (result_res_tx, result_res_rx) = async_channel::bounded(1);
-    run_async(async move {
-        post_result_task(result_req_rx, result_res_tx).await;
-    });
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// Fails with [`CommError::Timeout`] if `request` doesn't finish within [`REQUEST_TIMEOUT`].
+/// Done here instead of on the http client because reqwest has no timeouts on wasm.
+async fn with_timeout<T>(
+    request: impl Future<Output = Result<T, CommError>>,
+) -> Result<T, CommError> {
+    futures_lite::future::or(request, async {
+        sleep(REQUEST_TIMEOUT).await;
+        Err(CommError::Timeout)
+    })
+    .await
+}
+
+/// Retries `request` with exponential backoff while it fails with a transient error.
+/// Only use this for requests that are safe to send more than once.
+async fn with_retries<T, F, Fut>(mut request: F) -> Result<T, CommError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, CommError>>,
+{
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match with_timeout(request()).await {
+            Err(e) if e.is_transient() && attempt < MAX_ATTEMPTS => {
+                info!("request failed ({}), retrying in {:?}", e, delay);
+                sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
 
-    let (all_drawings_req_tx, all_drawings_req_rx) = async_channel::bounded(1);
-    let (all_drawings_res_tx, all_drawings_res_rx) = async_channel::bounded(1);
+pub type RequestId = u64;
 
-    run_async(async move { get_drawings_task(all_drawings_req_rx, all_drawings_res_tx).await });
+/// A request for the leaderboard backend. Equal requests in flight at the same time are only
+/// sent once, everyone who sent one gets the response.
+// only a handful are in flight at a time, boxing the entry isn't worth it
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    Submit(NewEntry),
+    List {
+        size: i64,
+        cursor: Option<String>,
+        challenge: Option<String>,
+    },
+    Get(String),
+    /// only the brush of an entry, for thumbnails
+    Brush(String),
+}
 
-    commands.insert_resource(CommChannels {
-        result_req_tx,
-        result_res_rx,
-        all_drawings_req_tx,
-        all_drawings_res_rx,
-    });
+#[derive(Clone, Debug)]
+pub enum Response {
+    Submit(Result<Entry, CommError>),
+    List(Result<Page, CommError>),
+    Get(Result<Option<Entry>, CommError>),
+    Brush(Result<Option<String>, CommError>),
 }
 
-// graphql query to write name to database
-#[allow(non_camel_case_types)] // must match name in graphql file
-#[derive(GraphQLQuery)]
-#[graphql(
-    schema_path = "graphql/schema.graphql",
-    query_path = "graphql/create_entry.graphql"
-)]
-pub struct createDrawings;
+struct InFlight {
+    request: Request,
+    /// how many callers sent it and haven't cancelled
+    holders: usize,
+    /// dropping this makes the worker abandon the request
+    _cancel_tx: Sender<()>,
+}
 
-async fn post_result_task(
-    result_req_rx: Receiver<DrawingsInput>,
-    result_res_tx: Sender<Result<create_drawings::CreateDrawingsCreateDrawings, String>>,
-) {
-    while let Ok(new_drawing) = result_req_rx.recv().await {
-        let result = async move {
-            let variables = create_drawings::Variables { new_drawing };
-
-            const FAUNA_API_TOKEN: &str = env!("UNFAIR_ADVANTAGE_PUBLIC_FAUNA_CLIENT_KEY");
-
-            let client = reqwest::Client::builder()
-                .default_headers(
-                    std::iter::once((
-                        reqwest::header::AUTHORIZATION,
-                        reqwest::header::HeaderValue::from_str(&format!(
-                            "Bearer {}",
-                            FAUNA_API_TOKEN,
-                        ))
-                        .unwrap(),
-                    ))
-                    .collect(),
-                )
-                .build()
-                .unwrap();
-
-            let res = post_graphql::<createDrawings, _>(
-                &client,
-                "https://graphql.fauna.com/graphql",
-                variables,
-            )
-            .await
-            .map_err(|e| e.to_string())?;
+struct Finished {
+    response: Response,
+    /// how many callers haven't taken or cancelled it yet
+    holders: usize,
+}
+
+/// Sends requests to the backend without blocking or panicking.
+/// Every request gets an id, its response is kept until taken with [`Comm::take`].
+pub struct Comm {
+    next_id: RequestId,
+    req_tx: Sender<(RequestId, Request, Receiver<()>)>,
+    res_rx: Receiver<(RequestId, Response)>,
+    in_flight: HashMap<RequestId, InFlight>,
+    finished: HashMap<RequestId, Finished>,
+}
+
+impl Comm {
+    /// Starts `request`, or returns the id of an equal request that is still in flight. Every
+    /// caller has to take or cancel the id it got.
+    pub fn send(&mut self, request: Request) -> RequestId {
+        if let Some((id, in_flight)) = self
+            .in_flight
+            .iter_mut()
+            .find(|(_, in_flight)| in_flight.request == request)
+        {
+            in_flight.holders += 1;
+            return *id;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let (cancel_tx, cancel_rx) = async_channel::bounded(1);
+        // unbounded, so this only fails if the worker is gone
+        if self
+            .req_tx
+            .try_send((id, request.clone(), cancel_rx))
+            .is_err()
+        {
+            error!("leaderboard worker is not running");
+            self.finished.insert(
+                id,
+                Finished {
+                    response: request.failed(CommError::Network("worker stopped".to_string())),
+                    holders: 1,
+                },
+            );
+            return id;
+        }
+        self.in_flight.insert(
+            id,
+            InFlight {
+                request,
+                holders: 1,
+                _cancel_tx: cancel_tx,
+            },
+        );
+        id
+    }
+
+    pub fn is_pending(&self, id: RequestId) -> bool {
+        self.in_flight.contains_key(&id)
+    }
+
+    /// Abandons the request, its response is dropped if it still arrives. Requests others sent
+    /// too keep going for them.
+    pub fn cancel(&mut self, id: RequestId) {
+        if let Some(in_flight) = self.in_flight.get_mut(&id) {
+            in_flight.holders -= 1;
+            if in_flight.holders == 0 {
+                self.in_flight.remove(&id);
+            }
+        } else if let Some(finished) = self.finished.get_mut(&id) {
+            finished.holders -= 1;
+            if finished.holders == 0 {
+                self.finished.remove(&id);
+            }
+        }
+    }
+
+    /// The response of the request, once it arrived. It is kept until everyone who sent the
+    /// request took it.
+    pub fn take(&mut self, id: RequestId) -> Option<Response> {
+        let finished = self.finished.get_mut(&id)?;
+        if finished.holders > 1 {
+            finished.holders -= 1;
+            return Some(finished.response.clone());
+        }
+        self.finished.remove(&id).map(|finished| finished.response)
+    }
+
+    pub fn take_submit(&mut self, id: RequestId) -> Option<Result<Entry, CommError>> {
+        match self.take(id)? {
+            Response::Submit(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    pub fn take_list(&mut self, id: RequestId) -> Option<Result<Page, CommError>> {
+        match self.take(id)? {
+            Response::List(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    pub fn take_get(&mut self, id: RequestId) -> Option<Result<Option<Entry>, CommError>> {
+        match self.take(id)? {
+            Response::Get(result) => Some(result),
+            _ => None,
+        }
+    }
 
-            if let Some(errors) = res.errors {
-                return Err(errors[0].to_string());
+    pub fn take_brush(&mut self, id: RequestId) -> Option<Result<Option<String>, CommError>> {
+        match self.take(id)? {
+            Response::Brush(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok((id, response)) = self.res_rx.try_recv() {
+            // responses of cancelled requests are dropped here
+            if let Some(in_flight) = self.in_flight.remove(&id) {
+                let holders = in_flight.holders;
+                self.finished.insert(id, Finished { response, holders });
             }
+        }
+    }
+}
 
-            Ok(res.data.unwrap().create_drawings)
+impl Request {
+    fn failed(&self, e: CommError) -> Response {
+        match self {
+            Request::Submit(_) => Response::Submit(Err(e)),
+            Request::List { .. } => Response::List(Err(e)),
+            Request::Get(_) => Response::Get(Err(e)),
+            Request::Brush(_) => Response::Brush(Err(e)),
         }
-        .await;
+    }
 
-        result_res_tx.send(result).await.unwrap();
+    async fn run(self, backend: &dyn LeaderboardBackend) -> Response {
+        match self {
+            // not retried, a lost response could otherwise create the entry twice
+            Request::Submit(entry) => Response::Submit(with_timeout(backend.submit(entry)).await),
+            Request::List {
+                size,
+                cursor,
+                challenge,
+            } => Response::List(
+                with_retries(|| backend.list(size, cursor.clone(), challenge.clone())).await,
+            ),
+            Request::Get(id) => Response::Get(with_retries(|| backend.get(&id)).await),
+            Request::Brush(id) => Response::Brush(with_retries(|| backend.brush(&id)).await),
+        }
     }
 }
 
-#[allow(non_camel_case_types)] // must match name in graphql file
-#[derive(GraphQLQuery)]
-#[graphql(
-    schema_path = "graphql/schema.graphql",
-    query_path = "graphql/all_drawings.graphql"
-)]
-pub struct allDrawings;
+fn setup_comm(mut commands: Commands) {
+    let backend = backend::from_env().unwrap_or_else(|e| {
+        error!("{}, falling back to fauna", e);
+        // the game always has the fauna key, `env!` fails the build otherwise
+        Arc::new(GraphQlBackend::fauna().unwrap())
+    });
+
+    let (req_tx, req_rx) = async_channel::unbounded();
+    let (res_tx, res_rx) = async_channel::unbounded();
+    run_async(async move {
+        worker_task(backend, req_rx, res_tx).await;
+    });
+
+    commands.insert_resource(Comm {
+        next_id: 0,
+        req_tx,
+        res_rx,
+        in_flight: HashMap::default(),
+        finished: HashMap::default(),
+    });
+}
 
-async fn get_drawings_task(
-    all_drawings_req_rx: Receiver<()>,
-    all_drawing_res_tx: Sender<Result<all_drawings::AllDrawingsAllDrawings, String>>,
+/// Runs every request in its own local task, so a slow request doesn't hold up the others
+async fn worker_task(
+    backend: Arc<dyn LeaderboardBackend>,
+    req_rx: Receiver<(RequestId, Request, Receiver<()>)>,
+    res_tx: Sender<(RequestId, Response)>,
 ) {
-    while all_drawings_req_rx.recv().await.is_ok() {
-        let result = async move {
-            const FAUNA_API_TOKEN: &str = env!("UNFAIR_ADVANTAGE_PUBLIC_FAUNA_CLIENT_KEY");
-
-            let client = reqwest::Client::builder()
-                .default_headers(
-                    std::iter::once((
-                        reqwest::header::AUTHORIZATION,
-                        reqwest::header::HeaderValue::from_str(&format!(
-                            "Bearer {}",
-                            FAUNA_API_TOKEN,
-                        ))
-                        .unwrap(),
-                    ))
-                    .collect(),
-                )
-                .build()
-                .unwrap();
-
-            let res = post_graphql::<allDrawings, _>(
-                &client,
-                "https://graphql.fauna.com/graphql",
-                all_drawings::Variables { size: 1000 },
+    while let Ok((id, request, cancel_rx)) = req_rx.recv().await {
+        let backend = backend.clone();
+        let res_tx = res_tx.clone();
+        tokio::task::spawn_local(async move {
+            let response = futures_lite::future::or(
+                async { Some(request.run(backend.as_ref()).await) },
+                async {
+                    // only returns once the sender is dropped
+                    let _ = cancel_rx.recv().await;
+                    None
+                },
             )
-            .await
-            .map_err(|e| e.to_string())?;
+            .await;
+            if let Some(response) = response {
+                // the game is shutting down if this fails
+                let _ = res_tx.send((id, response)).await;
+            }
+        });
+    }
+}
+
+fn receive_responses(comm: Option<ResMut<Comm>>) {
+    if let Some(mut comm) = comm {
+        comm.receive();
+    }
+}
+
+/// A result waiting in the queue
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedSubmission {
+    #[serde(flatten)]
+    pub entry: NewEntry,
+    /// `ts` of the run in the run history, marked as submitted once it is sent. Missing in
+    /// queues saved by older versions.
+    #[serde(default)]
+    pub run: Option<i64>,
+}
+
+/// A queued submission the leaderboard refused, kept until the player has seen why
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DroppedSubmission {
+    pub entry: NewEntry,
+    pub reason: String,
+}
+
+/// Submissions that could not be sent because the leaderboard was unreachable.
+/// They are kept in storage across restarts and sent again in the background.
+#[derive(Default)]
+pub struct SubmissionQueue {
+    pub entries: Vec<QueuedSubmission>,
+    /// refused by the leaderboard, shown in the start menu until dismissed
+    pub dropped: Vec<DroppedSubmission>,
+    pub last_error: Option<CommError>,
+    request: Option<RequestId>,
+    /// `Time::seconds_since_startup` of the next attempt
+    next_attempt: f64,
+}
+
+impl SubmissionQueue {
+    fn load() -> Self {
+        SubmissionQueue {
+            entries: storage::load(QUEUE_KEY).unwrap_or_default(),
+            dropped: storage::load(DROPPED_KEY).unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_sending(&self) -> bool {
+        self.request.is_some()
+    }
 
-            if let Some(errors) = res.errors {
-                return Err(errors[0].to_string());
+    /// Queues `entry`, the result of the run with `ts` `run` in the run history
+    pub fn push(&mut self, entry: NewEntry, run: Option<i64>) {
+        self.entries.push(QueuedSubmission { entry, run });
+        storage::save(QUEUE_KEY, &self.entries);
+    }
+
+    fn pop_front(&mut self) -> QueuedSubmission {
+        let queued = self.entries.remove(0);
+        storage::save(QUEUE_KEY, &self.entries);
+        queued
+    }
+
+    fn drop_front(&mut self, e: &CommError) {
+        let entry = self.pop_front().entry;
+        let reason = match e {
+            // the timed out attempt that queued it may have gone through after all
+            CommError::DuplicateName => format!(
+                "an entry named '{}' is already on the board, maybe this one from an earlier attempt",
+                entry.name
+            ),
+            e => e.to_string(),
+        };
+        self.dropped.push(DroppedSubmission { entry, reason });
+        storage::save(DROPPED_KEY, &self.dropped);
+    }
+
+    pub fn dismiss_dropped(&mut self) {
+        self.dropped.clear();
+        storage::save(DROPPED_KEY, &self.dropped);
+    }
+}
+
+fn send_queued_submissions(
+    time: Res<Time>,
+    mut queue: ResMut<SubmissionQueue>,
+    mut history: ResMut<RunHistory>,
+    comm: Option<ResMut<Comm>>,
+) {
+    // inserted by a startup system, so missing on the first frame
+    let mut comm = match comm {
+        Some(comm) => comm,
+        None => return,
+    };
+    let now = time.seconds_since_startup();
+
+    if let Some(id) = queue.request {
+        match comm.take_submit(id) {
+            Some(Ok(entry)) => {
+                info!("sent queued result of {}", entry.name);
+                queue.request = None;
+                // like a result sent from the results screen, so My Runs doesn't offer it again
+                if let Some(run) = queue.pop_front().run {
+                    history.mark_submitted(run);
+                }
+                queue.last_error = None;
             }
+            Some(Err(e)) if e.is_transient() => {
+                queue.request = None;
+                queue.next_attempt = now + QUEUE_RETRY_INTERVAL;
+                queue.last_error = Some(e);
+            }
+            Some(Err(e)) => {
+                // the backend refused it, sending it again won't help
+                warn!("dropping queued result: {}", e);
+                queue.request = None;
+                queue.drop_front(&e);
+                queue.last_error = Some(e);
+            }
+            None => {}
+        }
+    }
 
-            Ok(res.data.unwrap().all_drawings)
+    if queue.request.is_none() && now >= queue.next_attempt {
+        if let Some(queued) = queue.entries.first() {
+            queue.request = Some(comm.send(Request::Submit(queued.entry.clone())));
         }
-        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `Comm` without a worker, the test answers its requests
+    fn comm() -> (
+        Comm,
+        Receiver<(RequestId, Request, Receiver<()>)>,
+        Sender<(RequestId, Response)>,
+    ) {
+        let (req_tx, req_rx) = async_channel::unbounded();
+        let (res_tx, res_rx) = async_channel::unbounded();
+        let comm = Comm {
+            next_id: 0,
+            req_tx,
+            res_rx,
+            in_flight: HashMap::default(),
+            finished: HashMap::default(),
+        };
+        (comm, req_rx, res_tx)
+    }
+
+    fn submit() -> Request {
+        Request::Submit(NewEntry {
+            name: "painter".to_string(),
+            score: Some(50.0),
+            ..NewEntry::default()
+        })
+    }
+
+    fn answer(
+        comm: &mut Comm,
+        req_rx: &Receiver<(RequestId, Request, Receiver<()>)>,
+        res_tx: &Sender<(RequestId, Response)>,
+    ) {
+        let (id, _, _) = req_rx.try_recv().unwrap();
+        let entry = Entry {
+            id: "1".to_string(),
+            ..Entry::default()
+        };
+        res_tx.try_send((id, Response::Submit(Ok(entry)))).unwrap();
+        comm.receive();
+    }
+
+    #[test]
+    fn equal_requests_are_answered_for_every_caller() {
+        let (mut comm, req_rx, res_tx) = comm();
+        let first = comm.send(submit());
+        let second = comm.send(submit());
+        assert_eq!(first, second);
+        answer(&mut comm, &req_rx, &res_tx);
+        // only sent once
+        assert!(req_rx.try_recv().is_err());
+
+        assert!(matches!(comm.take_submit(first), Some(Ok(_))));
+        assert!(matches!(comm.take_submit(second), Some(Ok(_))));
+        assert!(comm.take_submit(first).is_none());
+    }
+
+    #[test]
+    fn cancelling_leaves_the_request_to_the_other_caller() {
+        let (mut comm, req_rx, res_tx) = comm();
+        let first = comm.send(submit());
+        let second = comm.send(submit());
+        comm.cancel(first);
+        assert!(comm.is_pending(second));
+        answer(&mut comm, &req_rx, &res_tx);
+        assert!(matches!(comm.take_submit(second), Some(Ok(_))));
 
-        all_drawing_res_tx.send(result).await.unwrap();
+        let third = comm.send(submit());
+        comm.cancel(third);
+        assert!(!comm.is_pending(third));
     }
 }
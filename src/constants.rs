@@ -3,3 +3,6 @@ use bevy::prelude::Color;
 pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 pub const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+pub const FONT: &str = "fonts/Archivo-Black.ttf";
+pub const SPLASH_IMAGE: &str = "images/Unfair_Start_Screen.png";
@@ -0,0 +1,108 @@
+//! The daily challenge: everyone gets the same brush and level for a UTC day and competes on
+//! a board of its own.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::scoring::{Level, LEVELS, MAX_SEED};
+
+const MICROS_PER_DAY: i64 = 24 * 60 * 60 * 1_000_000;
+const ID_PREFIX: &str = "daily-";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DailyChallenge {
+    /// days since the unix epoch
+    pub day: i64,
+    pub seed: u32,
+    pub level: &'static Level,
+}
+
+impl DailyChallenge {
+    pub fn for_day(day: i64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(day as u64);
+        DailyChallenge {
+            day,
+            seed: rng.gen_range(0..=MAX_SEED),
+            level: &LEVELS[day.rem_euclid(LEVELS.len() as i64) as usize],
+        }
+    }
+
+    pub fn today() -> Self {
+        Self::for_day(crate::backend::now_micros().div_euclid(MICROS_PER_DAY))
+    }
+
+    /// Parses the `challenge` field of an entry, e.g. `daily-2022-02-20`
+    pub fn from_id(id: &str) -> Option<Self> {
+        let mut parts = id.strip_prefix(ID_PREFIX)?.splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let challenge = Self::for_day(days_from_civil(year, month, day));
+        // rejects dates like the 31st of february
+        (challenge.id() == id).then_some(challenge)
+    }
+
+    /// Value of the `challenge` field for entries of this challenge
+    pub fn id(&self) -> String {
+        let (year, month, day) = civil_from_days(self.day);
+        format!("{}{:04}-{:02}-{:02}", ID_PREFIX, year, month, day)
+    }
+}
+
+// date conversions from http://howardhinnant.github.io/date_algorithms.html
+
+/// Year, month and day of the date `days` after the unix epoch
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(19_043), (2022, 2, 20));
+        // leap days, including the 400 year rule
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_417), (2023, 3, 1));
+        for day in -1_000_000..1_000_000 {
+            let (year, month, d) = civil_from_days(day);
+            assert_eq!(days_from_civil(year, month, d), day);
+        }
+    }
+
+    #[test]
+    fn challenge_ids_parse_back() {
+        let challenge = DailyChallenge::for_day(19_043);
+        assert_eq!(challenge.id(), "daily-2022-02-20");
+        assert_eq!(DailyChallenge::from_id(&challenge.id()), Some(challenge));
+        assert_eq!(DailyChallenge::from_id("daily-2022-02-31"), None);
+        assert_eq!(DailyChallenge::from_id("daily-2022-2-20"), None);
+        assert_eq!(DailyChallenge::from_id("timed"), None);
+    }
+}
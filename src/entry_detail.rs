@@ -0,0 +1,394 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use bevy_egui::{egui, EguiContext};
+use futures_lite::future;
+use unfair_coloring_competition::{
+    backend::{CommError, Entry},
+    daily::civil_from_days,
+    scoring::{
+        BrushShape, Level, Replay, ScoreBreakdown, BRUSH_IMAGE_SIZE, CANVAS_HEIGHT, CANVAS_WIDTH,
+        LEVELS, MAX_SEED,
+    },
+};
+
+use crate::{
+    comm::{Comm, Request, RequestId},
+    game_state::GameState,
+    textures::{TextureKey, TextureRegistry},
+};
+
+pub struct EntryDetailPlugin;
+impl Plugin for EntryDetailPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SelectedEntry::default())
+            .add_startup_system(load_target_images)
+            .add_system_set(
+                SystemSet::on_update(GameState::LeaderBoard)
+                    .with_system(receive_entry)
+                    .with_system(egui_ui),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::LeaderBoard).with_system(close));
+    }
+}
+
+/// Target image of every level, in the order of `LEVELS`
+pub struct TargetImages(Vec<Handle<Image>>);
+
+impl TargetImages {
+    pub fn of(&self, level: &Level) -> Option<&Handle<Image>> {
+        let index = LEVELS.iter().position(|l| l == level)?;
+        self.0.get(index)
+    }
+}
+
+/// The entry shown in the detail window
+#[derive(Default)]
+pub struct SelectedEntry {
+    id: Option<String>,
+    request: Option<RequestId>,
+    entry: Option<Entry>,
+    error: Option<CommError>,
+    shape: Option<BrushShape>,
+    /// rgba bytes of the painted canvas
+    canvas: Option<Vec<u8>>,
+    /// the replay being painted again on the compute pool, for entries without a stored canvas
+    painting: Option<Task<Vec<u8>>>,
+    breakdown: Option<ScoreBreakdown>,
+    /// the stored brush isn't a brush image
+    brush_damaged: bool,
+    /// neither the stored canvas nor the replay can be shown
+    drawing_damaged: bool,
+}
+
+impl SelectedEntry {
+    /// Opens the detail window for the entry with document id `id` and fetches it
+    pub fn select(
+        &mut self,
+        id: String,
+        comm: &mut Comm,
+        textures: &mut TextureRegistry,
+        egui_ctx: &mut EguiContext,
+    ) {
+        if self.id.as_ref() == Some(&id) {
+            return;
+        }
+        self.clear(comm, textures, egui_ctx);
+        self.request = Some(comm.send(Request::Get(id.clone())));
+        self.id = Some(id);
+    }
+
+    pub fn is_selected(&self, id: &str) -> bool {
+        self.id.as_deref() == Some(id)
+    }
+
+    fn clear(
+        &mut self,
+        comm: &mut Comm,
+        textures: &mut TextureRegistry,
+        egui_ctx: &mut EguiContext,
+    ) {
+        if let Some(request) = self.request.take() {
+            comm.cancel(request);
+        }
+        if let Some(id) = &self.id {
+            textures.unregister(&TextureKey::DetailBrush(id.clone()), egui_ctx);
+            textures.unregister(&TextureKey::Drawing(id.clone()), egui_ctx);
+        }
+        *self = SelectedEntry::default();
+    }
+}
+
+fn load_target_images(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = LEVELS
+        .iter()
+        .map(|level| asset_server.load(level.target))
+        .collect();
+    commands.insert_resource(TargetImages(handles));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn receive_entry(
+    mut comm: ResMut<Comm>,
+    mut selected: ResMut<SelectedEntry>,
+    pool: Res<AsyncComputeTaskPool>,
+    targets: Res<TargetImages>,
+    mut images: ResMut<Assets<Image>>,
+    mut textures: ResMut<TextureRegistry>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let selected = &mut *selected;
+    let result = selected.request.and_then(|id| comm.take_get(id));
+    match result {
+        Some(Ok(Some(entry))) => {
+            selected.request = None;
+            let shape = entry
+                .shape
+                .as_deref()
+                .and_then(|shape| serde_json::from_str::<BrushShape>(shape).ok())
+                .or_else(|| seed_of(&entry).map(BrushShape::from_seed));
+
+            // anything can be stored in an entry, an image of the wrong size would panic
+            if let Some(brush) = &entry.brush {
+                match base64::decode(brush) {
+                    Ok(brush) if brush.len() == BRUSH_IMAGE_SIZE * BRUSH_IMAGE_SIZE * 4 => {
+                        let handle =
+                            images.add(new_image(BRUSH_IMAGE_SIZE, BRUSH_IMAGE_SIZE, brush));
+                        textures.register(
+                            TextureKey::DetailBrush(entry.id.clone()),
+                            handle,
+                            &mut egui_ctx,
+                        );
+                    }
+                    _ => selected.brush_damaged = true,
+                }
+            }
+            if let Some(drawing) = &entry.drawing {
+                match base64::decode(drawing) {
+                    Ok(canvas) if canvas.len() == CANVAS_WIDTH * CANVAS_HEIGHT * 4 => {
+                        show_canvas(
+                            selected,
+                            &entry.id,
+                            canvas,
+                            &mut images,
+                            &mut textures,
+                            &mut egui_ctx,
+                        );
+                    }
+                    _ => selected.drawing_damaged = true,
+                }
+            } else if entry.replay.is_some() {
+                selected.painting = repaint(&entry, shape.clone(), &pool);
+                selected.drawing_damaged = selected.painting.is_none();
+            }
+
+            selected.entry = Some(entry);
+            selected.shape = shape;
+        }
+        Some(Ok(None)) => {
+            selected.request = None;
+            selected.error = Some(CommError::Validation(
+                "this entry no longer exists".to_string(),
+            ));
+        }
+        Some(Err(e)) => {
+            selected.request = None;
+            selected.error = Some(e);
+        }
+        None => {}
+    }
+
+    if let (Some(task), Some(id)) = (&mut selected.painting, selected.id.clone()) {
+        if let Some(canvas) = future::block_on(future::poll_once(task)) {
+            selected.painting = None;
+            show_canvas(
+                selected,
+                &id,
+                canvas,
+                &mut images,
+                &mut textures,
+                &mut egui_ctx,
+            );
+        }
+    }
+
+    // the target may still be loading when the entry arrives
+    let level = selected
+        .entry
+        .as_ref()
+        .map(|entry| entry.level.as_deref().unwrap_or(LEVELS[0].id))
+        .and_then(|id| LEVELS.iter().position(|level| level.id == id));
+    if selected.breakdown.is_none() {
+        let target = level.and_then(|level| images.get(&targets.0[level]));
+        if let (Some(canvas), Some(target)) = (&selected.canvas, target) {
+            selected.breakdown = Some(ScoreBreakdown::calculate(&target.data, canvas));
+        }
+    }
+}
+
+fn seed_of(entry: &Entry) -> Option<u32> {
+    entry
+        .seed
+        .and_then(|seed| u32::try_from(seed).ok())
+        .filter(|seed| *seed <= MAX_SEED)
+}
+
+/// Paints the replay of an entry again on the compute pool. `None` if it can't be read or is
+/// longer than the run could have recorded, painting that would stall the game.
+fn repaint(
+    entry: &Entry,
+    shape: Option<BrushShape>,
+    pool: &AsyncComputeTaskPool,
+) -> Option<Task<Vec<u8>>> {
+    let replay: Replay = serde_json::from_str(entry.replay.as_deref()?).ok()?;
+    if replay.0.len() > Replay::max_points(entry.duration) {
+        return None;
+    }
+    let shape = shape?;
+    Some(pool.spawn(async move { replay.distinct().simulate(&shape) }))
+}
+
+fn show_canvas(
+    selected: &mut SelectedEntry,
+    id: &str,
+    canvas: Vec<u8>,
+    images: &mut Assets<Image>,
+    textures: &mut TextureRegistry,
+    egui_ctx: &mut EguiContext,
+) {
+    let handle = images.add(new_image(CANVAS_WIDTH, CANVAS_HEIGHT, canvas.clone()));
+    textures.register(TextureKey::Drawing(id.to_string()), handle, egui_ctx);
+    selected.canvas = Some(canvas);
+}
+
+pub fn new_image(width: usize, height: usize, data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+    )
+}
+
+/// `_ts` as a UTC date and time
+pub fn format_timestamp(micros: i64) -> String {
+    let seconds = micros.div_euclid(1_000_000);
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60
+    )
+}
+
+fn egui_ui(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut selected: ResMut<SelectedEntry>,
+    mut comm: ResMut<Comm>,
+    mut textures: ResMut<TextureRegistry>,
+) {
+    let id = match selected.id.clone() {
+        Some(id) => id,
+        None => return,
+    };
+    let mut open = true;
+    egui::Window::new("Entry")
+        .open(&mut open)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            if let Some(e) = &selected.error {
+                ui.colored_label(egui::Color32::RED, e.to_string());
+                return;
+            }
+            let entry = match &selected.entry {
+                Some(entry) => entry,
+                None => {
+                    ui.label("Loading");
+                    return;
+                }
+            };
+
+            ui.heading(entry.name.as_str());
+            egui::Grid::new("entry_detail").show(ui, |ui| {
+                ui.label("score");
+                ui.label(format!("{:.1}", entry.score.unwrap_or_default()));
+                ui.end_row();
+                ui.label("submitted");
+                ui.label(format_timestamp(entry.ts));
+                ui.end_row();
+                ui.label("level");
+                ui.label(entry.level.as_deref().unwrap_or(LEVELS[0].id));
+                ui.end_row();
+                ui.label("mode");
+                ui.label(entry.mode.as_deref().unwrap_or("free"));
+                ui.end_row();
+                if let Some(duration) = entry.duration {
+                    ui.label("time painting");
+                    ui.label(format!("{:.1} s", duration));
+                    ui.end_row();
+                }
+                if let Some(remaining) = entry.remaining_time {
+                    ui.label("time left");
+                    ui.label(format!("{:.1} s", remaining));
+                    ui.end_row();
+                }
+                if let Some(version) = &entry.version {
+                    ui.label("game version");
+                    ui.label(version.as_str());
+                    ui.end_row();
+                }
+                ui.label("verified");
+                ui.label(match entry.verified {
+                    Some(true) => "yes",
+                    Some(false) => "no",
+                    None => "unknown",
+                });
+                ui.end_row();
+                if let Some(breakdown) = &selected.breakdown {
+                    ui.label("inside the duck");
+                    ui.label(format!("{} of {} px", breakdown.good, breakdown.max));
+                    ui.end_row();
+                    ui.label("outside the duck");
+                    ui.label(format!("{} px", breakdown.bad));
+                    ui.end_row();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if let Some(brush) = textures.get(&TextureKey::DetailBrush(id.clone())) {
+                    ui.image(brush, [125., 125.]);
+                }
+                if let Some(drawing) = textures.get(&TextureKey::Drawing(id.clone())) {
+                    ui.image(drawing, [250., 250.]);
+                } else if selected.painting.is_some() {
+                    ui.label("Painting the replay");
+                }
+            });
+            if selected.brush_damaged {
+                ui.label("The stored brush can't be shown.");
+            }
+            if selected.drawing_damaged {
+                ui.label("The painting can't be shown.");
+            }
+
+            if let Some(shape) = &selected.shape {
+                ui.collapsing("brush shape", |ui| {
+                    egui::Grid::new("brush_shape").show(ui, |ui| {
+                        ui.label("size");
+                        ui.label("offset");
+                        ui.end_row();
+                        for rect in &shape.rects {
+                            ui.label(format!("{:.0} x {:.0}", rect.extents[0], rect.extents[1]));
+                            ui.label(format!("{:.0}, {:.0}", rect.offset[0], rect.offset[1]));
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+        });
+
+    if !open {
+        selected.clear(&mut comm, &mut textures, &mut egui_ctx);
+    }
+}
+
+fn close(
+    mut selected: ResMut<SelectedEntry>,
+    mut comm: ResMut<Comm>,
+    mut textures: ResMut<TextureRegistry>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    selected.clear(&mut comm, &mut textures, &mut egui_ctx);
+}
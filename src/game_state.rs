@@ -3,6 +3,9 @@ pub enum GameState {
     Loading,
     StartMenu,
     Painting,
+    /// pushed on top of `Painting`
+    Paused,
     Results,
     LeaderBoard,
+    MyRuns,
 }
@@ -1,44 +1,369 @@
 use bevy::{
     prelude::*,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    utils::HashMap,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
+    window::WindowFocused,
 };
-use bevy_egui::{egui, EguiContext};
-use std::cmp::PartialOrd;
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Bar, BarChart, Plot},
+    },
+    EguiContext,
+};
+use futures_lite::future;
+use std::{cmp::Ordering, ops::RangeInclusive};
 
 use crate::{
-    comm::{
-        all_drawings::{self, AllDrawingsAllDrawingsData},
-        CommChannels,
-    },
+    comm::{Comm, Request, RequestId},
+    entry_detail::SelectedEntry,
     game_state::GameState,
+    painting::{Run, Score},
+    textures::{TextureKey, TextureRegistry},
+};
+use unfair_coloring_competition::{
+    backend::{self, rank_order, CommError, Entry},
+    daily::DailyChallenge,
+    scoring::{BRUSH_IMAGE_SIZE, LEVELS, TIMED_BOARD},
 };
 
+const MICROS_PER_DAY: i64 = 24 * 60 * 60 * 1_000_000;
+/// Ranks shown above and below the player's own entry in the "around me" view
+const AROUND_ME_RANKS: usize = 5;
+/// Brush thumbnails kept in memory
+const THUMBNAIL_CACHE_SIZE: usize = 64;
+/// Brushes fetched at the same time
+const MAX_THUMBNAIL_REQUESTS: usize = 8;
+const OWN_ENTRY_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 200, 0);
+const NEW_ENTRY_COLOR: egui::Color32 = egui::Color32::from_rgb(100, 220, 100);
+/// Seconds between refreshes while the window has focus
+const POLL_INTERVAL: f64 = 15.0;
+/// Refreshes slow down to this while the window is in the background
+const MAX_POLL_INTERVAL: f64 = 240.0;
+/// Seconds new entries and rank changes stay highlighted
+const HIGHLIGHT_SECONDS: f64 = 10.0;
+/// Rank changes announced per refresh
+const MAX_NOTICES: usize = 3;
+/// Score range covered by one bar of the statistics histogram
+const HISTOGRAM_BIN_WIDTH: f64 = 10.0;
+/// Scores outside are counted in the bar at the edge, so a forged score can't stretch the
+/// histogram
+const HISTOGRAM_SCORES: RangeInclusive<f64> = -100.0..=100.0;
+
 pub struct LeaderboardPlugin;
 impl Plugin for LeaderboardPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(AllDrawings(None))
-            .insert_resource(BrushHashmap(HashMap::default()))
+            .insert_resource(FetchError(None))
+            .insert_resource(FetchRequest(None))
+            .insert_resource(LeaderboardFilter::default())
+            .insert_resource(OwnEntry::default())
+            .insert_resource(ThumbnailCache::default())
+            .insert_resource(LiveUpdates::default())
+            .insert_resource(Tab::List)
+            .insert_resource(Board(None))
             .add_system_set(
                 SystemSet::on_enter(GameState::LeaderBoard).with_system(start_poll_leaderboard),
             )
             .add_system_set(
                 SystemSet::on_update(GameState::LeaderBoard)
-                    .with_system(egui_ui)
-                    .with_system(check_poll_leaderboard),
+                    .with_system(egui_ui.label("leaderboard_ui"))
+                    .with_system(stats_ui.after("leaderboard_ui"))
+                    .with_system(check_poll_leaderboard.label("check_poll_leaderboard"))
+                    .with_system(diff_leaderboard.after("check_poll_leaderboard"))
+                    .with_system(auto_refresh)
+                    .with_system(switch_board)
+                    .with_system(load_thumbnails),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::LeaderBoard)
+                    .with_system(cancel_poll_leaderboard)
+                    .with_system(cancel_thumbnails),
             );
     }
 }
 
+/// All entries, ordered by rank
 #[derive(Default)]
-struct AllDrawings(pub Option<Vec<all_drawings::AllDrawingsAllDrawingsData>>);
+struct AllDrawings(pub Option<Vec<Entry>>);
+
+/// Set when the last fetch failed, cleared when a new one starts
+struct FetchError(pub Option<CommError>);
+
+/// The list request in flight
+struct FetchRequest(Option<RequestId>);
+
+/// Challenge whose board is shown, `None` for the main board
+pub struct Board(pub Option<String>);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    List,
+    Stats,
+}
+
+/// Periodic refreshes and what changed since the last one
+struct LiveUpdates {
+    enabled: bool,
+    interval: f64,
+    next_poll: f64,
+    focused: bool,
+    /// board the ranks belong to, `None` before the first list was requested
+    board: Option<Option<String>>,
+    /// whether a list of the board arrived yet, nothing in the first one is new
+    loaded: bool,
+    /// rank of every entry in the last list, by document id
+    ranks: HashMap<String, usize>,
+    /// entries added since the last refresh, with the time their highlight ends
+    new_entries: HashMap<String, f64>,
+    notices: Vec<(String, f64)>,
+}
+
+impl Default for LiveUpdates {
+    fn default() -> Self {
+        LiveUpdates {
+            enabled: false,
+            interval: POLL_INTERVAL,
+            next_poll: 0.0,
+            focused: true,
+            board: None,
+            loaded: false,
+            ranks: HashMap::default(),
+            new_entries: HashMap::default(),
+            notices: Vec::new(),
+        }
+    }
+}
+
+impl LiveUpdates {
+    /// Forgets everything known about the previous board. Returns false if `board` is the
+    /// board already shown.
+    fn start_over(&mut self, board: &Board) -> bool {
+        if self.board.as_ref() == Some(&board.0) {
+            return false;
+        }
+        self.board = Some(board.0.clone());
+        self.loaded = false;
+        self.ranks.clear();
+        self.new_entries.clear();
+        self.notices.clear();
+        true
+    }
+}
+
+/// The entry the player submitted this session, highlighted on the leaderboard
+#[derive(Default)]
+pub struct OwnEntry {
+    id: Option<String>,
+    around_me: bool,
+    /// scroll to the entry the next time it is drawn
+    scroll_to: bool,
+}
 
+impl OwnEntry {
+    pub fn set(&mut self, id: String) {
+        self.id = Some(id);
+        self.scroll_to = true;
+    }
+
+    fn is(&self, entry: &Entry) -> bool {
+        self.id.as_deref() == Some(entry.id.as_str())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DateRange {
+    AllTime,
+    Today,
+    Week,
+    Month,
+}
+
+impl DateRange {
+    const ALL: [DateRange; 4] = [
+        DateRange::AllTime,
+        DateRange::Today,
+        DateRange::Week,
+        DateRange::Month,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            DateRange::AllTime => "All time",
+            DateRange::Today => "Last 24 hours",
+            DateRange::Week => "Last 7 days",
+            DateRange::Month => "Last 30 days",
+        }
+    }
+
+    /// Earliest `_ts` inside the range
+    fn since(self, now: i64) -> Option<i64> {
+        let days = match self {
+            DateRange::AllTime => return None,
+            DateRange::Today => 1,
+            DateRange::Week => 7,
+            DateRange::Month => 30,
+        };
+        Some(now - days * MICROS_PER_DAY)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Score,
+    Newest,
+}
+
+/// What the player narrowed the leaderboard down to. Ranks stay the overall ranks.
+struct LeaderboardFilter {
+    search: String,
+    /// level id, `None` for all levels
+    level: Option<&'static str>,
+    range: DateRange,
+    sort: SortOrder,
+}
+
+impl Default for LeaderboardFilter {
+    fn default() -> Self {
+        LeaderboardFilter {
+            search: String::new(),
+            level: None,
+            range: DateRange::AllTime,
+            sort: SortOrder::Score,
+        }
+    }
+}
+
+impl LeaderboardFilter {
+    /// Rank and entry of the entries that pass the filter, in display order
+    fn apply<'a>(&self, drawings: &'a [Entry], now: i64) -> Vec<(usize, &'a Entry)> {
+        let search = self.search.trim().to_lowercase();
+        let since = self.range.since(now);
+        let mut shown: Vec<(usize, &Entry)> = drawings
+            .iter()
+            .enumerate()
+            .map(|(n, entry)| (n + 1, entry))
+            .filter(|(_, entry)| search.is_empty() || entry.name.to_lowercase().contains(&search))
+            .filter(|(_, entry)| self.level.is_none_or(|level| level_of(entry) == level))
+            .filter(|(_, entry)| since.is_none_or(|since| entry.ts >= since))
+            .collect();
+        if self.sort == SortOrder::Newest {
+            // stable, so entries with the same timestamp keep their rank order
+            shown.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.ts));
+        }
+        shown
+    }
+}
+
+/// Entries from before levels were recorded were all painted on the first level
+fn level_of(entry: &Entry) -> &str {
+    entry.level.as_deref().unwrap_or(LEVELS[0].id)
+}
+
+fn filter_ui(ui: &mut egui::Ui, filter: &mut LeaderboardFilter) {
+    ui.horizontal(|ui| {
+        ui.label("Search:");
+        ui.text_edit_singleline(&mut filter.search);
+
+        egui::ComboBox::from_id_source("level")
+            .selected_text(filter.level.unwrap_or("All levels"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.level, None, "All levels");
+                for level in LEVELS {
+                    ui.selectable_value(&mut filter.level, Some(level.id), level.id);
+                }
+            });
+
+        egui::ComboBox::from_id_source("date_range")
+            .selected_text(filter.range.label())
+            .show_ui(ui, |ui| {
+                for range in DateRange::ALL {
+                    ui.selectable_value(&mut filter.range, range, range.label());
+                }
+            });
+
+        ui.label("Sort by:");
+        ui.selectable_value(&mut filter.sort, SortOrder::Score, "Score");
+        ui.selectable_value(&mut filter.sort, SortOrder::Newest, "Newest");
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
 fn egui_ui(
     mut egui_ctx: ResMut<EguiContext>,
-    mut all: ResMut<AllDrawings>,
-    brush_hashmap: Res<BrushHashmap>,
+    all: Res<AllDrawings>,
+    mut thumbnails: ResMut<ThumbnailCache>,
+    mut textures: ResMut<TextureRegistry>,
     mut state: ResMut<State<GameState>>,
+    mut fetch_error: ResMut<FetchError>,
+    mut comm: ResMut<Comm>,
+    mut fetch_request: ResMut<FetchRequest>,
+    mut filter: ResMut<LeaderboardFilter>,
+    mut own_entry: ResMut<OwnEntry>,
+    mut selected: ResMut<SelectedEntry>,
+    mut live: ResMut<LiveUpdates>,
+    mut tab: ResMut<Tab>,
+    mut board: ResMut<Board>,
 ) {
+    let own_rank = all.0.as_ref().and_then(|drawings| {
+        drawings
+            .iter()
+            .position(|entry| own_entry.is(entry))
+            .map(|n| n + 1)
+    });
+
+    egui::TopBottomPanel::top("filters").show(egui_ctx.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut *tab, Tab::List, "Leaderboard");
+            ui.selectable_value(&mut *tab, Tab::Stats, "Statistics");
+            ui.separator();
+            let daily = Some(DailyChallenge::today().id());
+            let on_main = board.0.is_none();
+            if ui.selectable_label(on_main, "Main board").clicked() && !on_main {
+                board.0 = None;
+            }
+            if ui
+                .selectable_label(board.0 == daily, "Today's daily")
+                .clicked()
+                && board.0 != daily
+            {
+                board.0 = daily;
+            }
+            let timed = Some(TIMED_BOARD.to_string());
+            if ui.selectable_label(board.0 == timed, "Timed").clicked() && board.0 != timed {
+                board.0 = timed;
+            }
+        });
+        if let (Some(rank), Some(drawings)) = (own_rank, &all.0) {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    OWN_ENTRY_COLOR,
+                    format!("You placed #{} of {}", rank, drawings.len()),
+                );
+                ui.checkbox(&mut own_entry.around_me, "Around me");
+            });
+        }
+        filter_ui(ui, &mut filter);
+        ui.checkbox(&mut live.enabled, "Auto refresh");
+        for (notice, _) in &live.notices {
+            ui.label(notice.as_str());
+        }
+    });
+
+    egui::SidePanel::right("side_panel")
+        .default_width(100.)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            if ui.button("Back to Start Menu").clicked() {
+                // fails if a state change is already queued, the click is dropped then
+                let _ = state.set(GameState::StartMenu);
+            }
+        });
+
+    if *tab != Tab::List {
+        return;
+    }
+    let mut clicked = None;
     egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
         egui::ScrollArea::vertical().show(ui, |ui| {
             egui::Grid::new("leaderboard").show(ui, |ui| {
@@ -48,27 +373,72 @@ fn egui_ui(
                 ui.label("brush");
                 ui.end_row();
 
-                if let Some(mut drawings) = all.0.take() {
-                    drawings.sort_by(|a, b| {
-                        b.score
-                            .unwrap()
-                            .partial_cmp(&a.score.unwrap())
-                            .unwrap_or(std::cmp::Ordering::Equal)
-                    });
+                if let Some(drawings) = &all.0 {
+                    let mut shown = filter.apply(drawings, backend::now_micros());
+                    if let (true, Some(own_rank)) = (own_entry.around_me, own_rank) {
+                        shown.retain(|(rank, _)| {
+                            (*rank as isize - own_rank as isize).unsigned_abs() <= AROUND_ME_RANKS
+                        });
+                    }
                     if drawings.is_empty() {
                         ui.label("No Entries");
+                    } else if shown.is_empty() {
+                        ui.label("No matching entries");
                     } else {
-                        for (rank, result) in drawings.iter().enumerate() {
-                            ui.label(format!("{}", rank + 1));
-                            ui.label(result.name.clone());
-                            ui.label(format!("{:.1}", result.score.unwrap().clone()));
-                            if let Some(image) = brush_hashmap.0.get(&result.name) {
-                                ui.image(egui::TextureId::User(image.egui_id), [50., 50.]);
+                        for (rank, result) in shown {
+                            let own = own_entry.is(result);
+                            let color = if own {
+                                OWN_ENTRY_COLOR
+                            } else if live.new_entries.contains_key(&result.id) {
+                                NEW_ENTRY_COLOR
+                            } else {
+                                ui.visuals().text_color()
+                            };
+                            let rank_label = ui.colored_label(color, format!("{}", rank));
+                            if ui
+                                .selectable_label(
+                                    selected.is_selected(&result.id),
+                                    result.name.clone(),
+                                )
+                                .clicked()
+                            {
+                                clicked = Some(result.id.clone());
+                            }
+                            ui.colored_label(
+                                color,
+                                format!("{:.1}", result.score.unwrap_or_default()),
+                            );
+                            if own && own_entry.scroll_to {
+                                rank_label.scroll_to_me(egui::Align::Center);
+                                own_entry.scroll_to = false;
+                            }
+
+                            // only rows on screen load their brush
+                            let visible = ui.clip_rect().intersects(rank_label.rect);
+                            let thumbnail = visible
+                                .then(|| thumbnails.get(&result.id, &textures))
+                                .flatten();
+                            match thumbnail {
+                                Some(texture) => {
+                                    ui.image(texture, [50., 50.]);
+                                }
+                                None => {
+                                    ui.allocate_space(egui::vec2(50., 50.));
+                                }
                             }
                             ui.end_row();
                         }
                     }
-                    all.0 = Some(drawings);
+                } else if let Some(e) = fetch_error.0.clone() {
+                    ui.colored_label(egui::Color32::RED, e.to_string());
+                    let pending = fetch_request.0.is_some_and(|id| comm.is_pending(id));
+                    if ui
+                        .add_enabled(!pending, egui::Button::new("Retry"))
+                        .clicked()
+                    {
+                        fetch_error.0 = None;
+                        fetch_request.0 = Some(comm.send(list_request(&board)));
+                    }
                 } else {
                     ui.label("Loading");
                 }
@@ -76,64 +446,457 @@ fn egui_ui(
         });
     });
 
-    egui::SidePanel::right("side_panel")
-        .default_width(100.)
-        .show(egui_ctx.ctx_mut(), |ui| {
-            if ui.button("Back to Start Menu").clicked() {
-                state.set(GameState::StartMenu).unwrap();
+    if let Some(id) = clicked {
+        selected.select(id, &mut comm, &mut textures, &mut egui_ctx);
+    }
+}
+
+fn list_request(board: &Board) -> Request {
+    Request::List {
+        size: 1000,
+        cursor: None,
+        challenge: board.0.clone(),
+    }
+}
+
+/// Fetches the other board when the player switches, comparing ranks across boards makes no
+/// sense so the live updates start over
+fn switch_board(
+    board: Res<Board>,
+    mut comm: ResMut<Comm>,
+    mut fetch_request: ResMut<FetchRequest>,
+    mut fetch_error: ResMut<FetchError>,
+    mut all: ResMut<AllDrawings>,
+    mut live: ResMut<LiveUpdates>,
+) {
+    // the board is also marked as changed when the state is entered, start_poll_leaderboard
+    // already asked for that one
+    if !board.is_changed() || !live.start_over(&board) {
+        return;
+    }
+    if let Some(id) = fetch_request.0.take() {
+        comm.cancel(id);
+    }
+    fetch_error.0 = None;
+    all.0 = None;
+    fetch_request.0 = Some(comm.send(list_request(&board)));
+}
+
+fn start_poll_leaderboard(
+    time: Res<Time>,
+    mut comm: ResMut<Comm>,
+    mut fetch_error: ResMut<FetchError>,
+    mut fetch_request: ResMut<FetchRequest>,
+    mut live: ResMut<LiveUpdates>,
+    mut all: ResMut<AllDrawings>,
+    board: Res<Board>,
+) {
+    // the results screen may have picked another board since the leaderboard was last shown
+    if live.start_over(&board) {
+        all.0 = None;
+    }
+    fetch_error.0 = None;
+    fetch_request.0 = Some(comm.send(list_request(&board)));
+    live.next_poll = time.seconds_since_startup() + live.interval;
+}
+
+/// Fetches the list again every `POLL_INTERVAL`, backing off while the window is unfocused
+fn auto_refresh(
+    time: Res<Time>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut live: ResMut<LiveUpdates>,
+    mut comm: ResMut<Comm>,
+    mut fetch_request: ResMut<FetchRequest>,
+    board: Res<Board>,
+) {
+    let now = time.seconds_since_startup();
+    for event in focus_events.iter() {
+        live.focused = event.focused;
+        if event.focused {
+            // catch up right away when the player comes back
+            live.interval = POLL_INTERVAL;
+            live.next_poll = live.next_poll.min(now);
+        }
+    }
+
+    live.new_entries.retain(|_, until| *until > now);
+    live.notices.retain(|(_, until)| *until > now);
+
+    if !live.enabled || fetch_request.0.is_some() || now < live.next_poll {
+        return;
+    }
+    fetch_request.0 = Some(comm.send(list_request(&board)));
+    if !live.focused {
+        live.interval = (live.interval * 2.0).min(MAX_POLL_INTERVAL);
+    }
+    live.next_poll = now + live.interval;
+}
+
+/// Compares a freshly fetched list with the previous one
+fn diff_leaderboard(
+    time: Res<Time>,
+    all: Res<AllDrawings>,
+    own_entry: Res<OwnEntry>,
+    mut live: ResMut<LiveUpdates>,
+) {
+    if !all.is_changed() {
+        return;
+    }
+    let drawings = match &all.0 {
+        Some(drawings) => drawings,
+        None => return,
+    };
+    let live = &mut *live;
+    let until = time.seconds_since_startup() + HIGHLIGHT_SECONDS;
+    // the first list has nothing to compare with, an empty board is loaded all the same
+    let first = !live.loaded;
+    live.loaded = true;
+
+    let mut moves = Vec::new();
+    for (n, entry) in drawings.iter().enumerate() {
+        let rank = n + 1;
+        match live.ranks.get(&entry.id) {
+            Some(&previous) if previous != rank => {
+                moves.push((entry, previous as isize - rank as isize));
             }
-        });
+            Some(_) => {}
+            None if !first => {
+                live.new_entries.insert(entry.id.clone(), until);
+            }
+            None => {}
+        }
+    }
+    // the player's own entry first, then the biggest moves
+    moves.sort_by_key(|(entry, places)| (!own_entry.is(entry), -places.abs()));
+    for (entry, places) in moves.into_iter().take(MAX_NOTICES) {
+        let who = if own_entry.is(entry) {
+            "You".to_string()
+        } else {
+            entry.name.clone()
+        };
+        let direction = if places > 0 { "up" } else { "down" };
+        let count = places.unsigned_abs();
+        let notice = format!(
+            "{} moved {} {} place{}",
+            who,
+            direction,
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        live.notices.push((notice, until));
+    }
+
+    live.ranks = drawings
+        .iter()
+        .enumerate()
+        .map(|(n, entry)| (entry.id.clone(), n + 1))
+        .collect();
 }
 
-fn start_poll_leaderboard(comm_channels: ResMut<CommChannels>) {
-    comm_channels.all_drawings_req_tx.try_send(()).unwrap();
+fn cancel_poll_leaderboard(mut comm: ResMut<Comm>, mut fetch_request: ResMut<FetchRequest>) {
+    if let Some(id) = fetch_request.0.take() {
+        comm.cancel(id);
+    }
+}
+
+/// Brush thumbnails of the rows that were on screen recently, keyed by document id.
+/// Brushes are fetched when their row is first drawn, the least recently drawn ones are
+/// dropped once the cache is full.
+#[derive(Default)]
+struct ThumbnailCache {
+    /// `frame` each cached thumbnail was last drawn in
+    last_used: HashMap<String, u64>,
+    /// drawn rows without a thumbnail, filled by the ui every frame
+    wanted: Vec<String>,
+    /// entries whose brush is missing, broken or could not be fetched. Not asked for again
+    /// until the board is fetched again.
+    failed: HashSet<String>,
+    requests: HashMap<String, RequestId>,
+    decoding: HashMap<String, Task<Option<Image>>>,
+    frame: u64,
 }
 
-struct BrushEguiImage {
-    #[allow(dead_code)] // handle is saved here to prevent unload
-    handle: Handle<Image>,
-    egui_id: u64,
+impl ThumbnailCache {
+    /// Texture of the thumbnail, marking it as used
+    fn get(&mut self, id: &str, textures: &TextureRegistry) -> Option<egui::TextureId> {
+        match textures.get(&TextureKey::Brush(id.to_string())) {
+            Some(texture) => {
+                self.last_used.insert(id.to_string(), self.frame);
+                Some(texture)
+            }
+            None => {
+                if !self.failed.contains(id) {
+                    self.wanted.push(id.to_string());
+                }
+                None
+            }
+        }
+    }
+
+    fn insert(
+        &mut self,
+        id: String,
+        handle: Handle<Image>,
+        textures: &mut TextureRegistry,
+        egui_ctx: &mut EguiContext,
+    ) {
+        if self.last_used.len() >= THUMBNAIL_CACHE_SIZE {
+            let oldest = self
+                .last_used
+                .iter()
+                .min_by_key(|(_, last_used)| **last_used)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                self.remove(&oldest, textures, egui_ctx);
+            }
+        }
+        textures.register(TextureKey::Brush(id.clone()), handle, egui_ctx);
+        self.last_used.insert(id, self.frame);
+    }
+
+    fn remove(&mut self, id: &str, textures: &mut TextureRegistry, egui_ctx: &mut EguiContext) {
+        self.last_used.remove(id);
+        textures.unregister(&TextureKey::Brush(id.to_string()), egui_ctx);
+    }
 }
-struct BrushHashmap(pub HashMap<String, BrushEguiImage>);
 
 fn check_poll_leaderboard(
-    comm_channels: ResMut<CommChannels>,
+    mut comm: ResMut<Comm>,
+    mut fetch_request: ResMut<FetchRequest>,
     mut all: ResMut<AllDrawings>,
-    mut images: ResMut<Assets<Image>>,
+    mut fetch_error: ResMut<FetchError>,
+    mut thumbnails: ResMut<ThumbnailCache>,
+    mut textures: ResMut<TextureRegistry>,
     mut egui_ctx: ResMut<EguiContext>,
-    mut brush_hashmap: ResMut<BrushHashmap>,
 ) {
-    if let Ok(result) = comm_channels.all_drawings_res_rx.try_recv() {
+    let result = fetch_request.0.and_then(|id| comm.take_list(id));
+    if let Some(result) = result {
+        fetch_request.0 = None;
         match result {
             Ok(all_drawings) => {
-                let mut temp: Vec<AllDrawingsAllDrawingsData> =
-                    all_drawings.data.into_iter().flatten().collect();
-                for (n, entry) in temp.iter_mut().enumerate() {
-                    if let Some(brush) = entry.brush.take() {
-                        let image = Image::new(
-                            Extent3d {
-                                width: 250,
-                                height: 250,
-                                ..Default::default()
-                            },
-                            TextureDimension::D2,
-                            base64::decode(&brush).unwrap(),
-                            TextureFormat::Rgba8Unorm,
-                        );
-                        let handle = images.add(image);
-                        brush_hashmap.0.insert(
-                            entry.name.clone(),
-                            BrushEguiImage {
-                                handle: handle.clone(),
-                                egui_id: (n + 1) as u64,
-                            },
-                        );
-                        egui_ctx.set_egui_texture((n + 1) as u64, handle);
-                    }
+                let mut temp: Vec<Entry> = all_drawings
+                    .data
+                    .into_iter()
+                    .filter(Entry::is_ranked)
+                    .collect();
+                temp.sort_by(rank_order);
+                // free the brushes of entries that are gone
+                let gone: Vec<String> = thumbnails
+                    .last_used
+                    .keys()
+                    .filter(|id| !temp.iter().any(|entry| &entry.id == *id))
+                    .cloned()
+                    .collect();
+                for id in gone {
+                    thumbnails.remove(&id, &mut textures, &mut egui_ctx);
                 }
+                // give brushes that failed to load another chance
+                thumbnails.failed.clear();
                 all.0 = Some(temp);
             }
-            Err(e) => info!("{}", e),
+            Err(e) => {
+                info!("{}", e);
+                fetch_error.0 = Some(e);
+            }
         }
     }
 }
+
+/// Score distribution of the fetched entries, drawn instead of the list on the stats tab
+#[allow(clippy::too_many_arguments)]
+fn stats_ui(
+    mut egui_ctx: ResMut<EguiContext>,
+    tab: Res<Tab>,
+    all: Res<AllDrawings>,
+    own_entry: Res<OwnEntry>,
+    filter: Res<LeaderboardFilter>,
+    board: Res<Board>,
+    last_run: Option<Res<Run>>,
+    last_score: Option<Res<Score>>,
+) {
+    if *tab != Tab::Stats {
+        return;
+    }
+    egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        let drawings = match &all.0 {
+            Some(drawings) if !drawings.is_empty() => drawings,
+            Some(_) => {
+                ui.label("No Entries");
+                return;
+            }
+            None => {
+                ui.label("Loading");
+                return;
+            }
+        };
+        // entries of the chosen level only, so scores of different targets aren't mixed
+        let mut scores: Vec<f64> = drawings
+            .iter()
+            .filter(|entry| filter.level.is_none_or(|level| level_of(entry) == level))
+            .filter_map(|entry| entry.score)
+            .filter(|score| score.is_finite())
+            .collect();
+        scores.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        if scores.is_empty() {
+            ui.label("No Entries");
+            return;
+        }
+
+        let middle = scores.len() / 2;
+        let median = if scores.len().is_multiple_of(2) {
+            (scores[middle - 1] + scores[middle]) / 2.0
+        } else {
+            scores[middle]
+        };
+        ui.label(format!(
+            "{} entries, median score {:.1}",
+            scores.len(),
+            median
+        ));
+
+        // the last run only compares with the board and level it was played for
+        let run_fits = last_run.is_some_and(|run| {
+            run.challenge == board.0 && filter.level.is_none_or(|level| run.level.id == level)
+        });
+        // the submitted entry if there is one, otherwise the last run
+        let own_score = drawings
+            .iter()
+            .find(|entry| own_entry.is(entry))
+            .and_then(|entry| entry.score)
+            .or_else(|| last_score.filter(|_| run_fits).map(|score| score.0));
+        if let Some(own_score) = own_score {
+            let below = scores.iter().filter(|score| **score < own_score).count();
+            let percentile = below as f64 / scores.len() as f64 * 100.0;
+            ui.colored_label(
+                OWN_ENTRY_COLOR,
+                format!(
+                    "Your score of {:.1} beats {:.0}% of all entries",
+                    own_score, percentile
+                ),
+            );
+        }
+
+        let bin = |score: f64| {
+            let score = score.clamp(*HISTOGRAM_SCORES.start(), *HISTOGRAM_SCORES.end());
+            (score / HISTOGRAM_BIN_WIDTH).floor() as i64
+        };
+        let lowest = bin(scores[0]);
+        let highest = bin(scores[scores.len() - 1]);
+        let mut counts = vec![0u32; (highest - lowest + 1) as usize];
+        for score in &scores {
+            counts[(bin(*score) - lowest) as usize] += 1;
+        }
+        let bars = counts
+            .iter()
+            .enumerate()
+            .map(|(n, count)| {
+                let start = (lowest + n as i64) as f64 * HISTOGRAM_BIN_WIDTH;
+                let mut bar = Bar::new(start + HISTOGRAM_BIN_WIDTH / 2.0, *count as f64)
+                    .width(HISTOGRAM_BIN_WIDTH * 0.9);
+                if own_score.is_some_and(|own| bin(own) == lowest + n as i64) {
+                    bar = bar.fill(OWN_ENTRY_COLOR);
+                }
+                bar
+            })
+            .collect();
+        Plot::new("score_histogram")
+            .allow_drag(false)
+            .allow_zoom(false)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(bars).name("entries"));
+            });
+    });
+}
+
+/// Fetches the brushes of drawn rows, decodes them on the compute pool and caches the results
+fn load_thumbnails(
+    mut cache: ResMut<ThumbnailCache>,
+    mut comm: ResMut<Comm>,
+    pool: Res<AsyncComputeTaskPool>,
+    mut images: ResMut<Assets<Image>>,
+    mut textures: ResMut<TextureRegistry>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let cache = &mut *cache;
+    cache.frame += 1;
+
+    for id in std::mem::take(&mut cache.wanted) {
+        if cache.requests.len() >= MAX_THUMBNAIL_REQUESTS {
+            break;
+        }
+        if !cache.requests.contains_key(&id) && !cache.decoding.contains_key(&id) {
+            let request = comm.send(Request::Brush(id.clone()));
+            cache.requests.insert(id, request);
+        }
+    }
+
+    let mut finished = Vec::new();
+    for (id, request) in &cache.requests {
+        match comm.take_brush(*request) {
+            Some(Ok(Some(brush))) => {
+                finished.push(id.clone());
+                let task = pool.spawn(async move { decode_thumbnail(brush) });
+                cache.decoding.insert(id.clone(), task);
+            }
+            // deleted since the board was fetched, or stored without a brush
+            Some(Ok(None)) => {
+                finished.push(id.clone());
+                cache.failed.insert(id.clone());
+            }
+            Some(Err(e)) => {
+                info!("could not load brush: {}", e);
+                finished.push(id.clone());
+                cache.failed.insert(id.clone());
+            }
+            None => {}
+        }
+    }
+    for id in finished {
+        cache.requests.remove(&id);
+    }
+
+    let mut decoded = Vec::new();
+    for (id, task) in cache.decoding.iter_mut() {
+        if let Some(image) = future::block_on(future::poll_once(task)) {
+            decoded.push((id.clone(), image));
+        }
+    }
+    for (id, image) in decoded {
+        cache.decoding.remove(&id);
+        match image {
+            Some(image) => {
+                let handle = images.add(image);
+                cache.insert(id, handle, &mut textures, &mut egui_ctx);
+            }
+            None => {
+                cache.failed.insert(id);
+            }
+        }
+    }
+}
+
+fn decode_thumbnail(brush: String) -> Option<Image> {
+    let data = base64::decode(&brush).ok()?;
+    if data.len() != BRUSH_IMAGE_SIZE * BRUSH_IMAGE_SIZE * 4 {
+        return None;
+    }
+    Some(Image::new(
+        Extent3d {
+            width: BRUSH_IMAGE_SIZE as u32,
+            height: BRUSH_IMAGE_SIZE as u32,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+    ))
+}
+
+/// Rows left the screen, stop fetching their brushes
+fn cancel_thumbnails(mut cache: ResMut<ThumbnailCache>, mut comm: ResMut<Comm>) {
+    for (_, request) in cache.requests.drain() {
+        comm.cancel(request);
+    }
+    cache.decoding.clear();
+    cache.wanted.clear();
+}
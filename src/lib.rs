@@ -0,0 +1,6 @@
+//! Code shared between the game and the leaderboard server and tools.
+
+pub mod backend;
+pub mod daily;
+pub mod nickname;
+pub mod scoring;
@@ -0,0 +1,82 @@
+use bevy::{asset::LoadState, prelude::*};
+use bevy_egui::{egui, EguiContext};
+use unfair_coloring_competition::scoring::LEVELS;
+
+use crate::{
+    constants::{FONT, SPLASH_IMAGE},
+    game_state::GameState,
+};
+
+pub struct LoadingPlugin;
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Preloaded>()
+            .add_system_set(SystemSet::on_enter(GameState::Loading).with_system(start_loading))
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading)
+                    .with_system(check_loading)
+                    .with_system(egui_ui),
+            );
+    }
+}
+
+/// Assets every screen relies on. The handles are kept for the whole game so nothing has to
+/// be loaded again later.
+#[derive(Default)]
+struct Preloaded {
+    assets: Vec<(&'static str, HandleUntyped)>,
+    loaded: usize,
+    /// paths that could not be loaded
+    failed: Vec<&'static str>,
+}
+
+fn start_loading(mut preloaded: ResMut<Preloaded>, asset_server: Res<AssetServer>) {
+    let paths = [FONT, SPLASH_IMAGE]
+        .into_iter()
+        .chain(LEVELS.iter().map(|level| level.target));
+    preloaded.assets = paths
+        .map(|path| (path, asset_server.load_untyped(path)))
+        .collect();
+}
+
+fn check_loading(
+    asset_server: Res<AssetServer>,
+    mut preloaded: ResMut<Preloaded>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let mut loaded = 0;
+    let mut failed = Vec::new();
+    for (path, handle) in &preloaded.assets {
+        match asset_server.get_load_state(handle.id) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => failed.push(*path),
+            _ => {}
+        }
+    }
+    preloaded.loaded = loaded;
+    preloaded.failed = failed;
+    // retried next frame if another state change was queued
+    if loaded == preloaded.assets.len() {
+        let _ = state.set(GameState::StartMenu);
+    }
+}
+
+fn egui_ui(mut egui_ctx: ResMut<EguiContext>, preloaded: Res<Preloaded>) {
+    egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(250.);
+            if preloaded.failed.is_empty() {
+                ui.heading("Loading");
+                let progress = preloaded.loaded as f32 / preloaded.assets.len().max(1) as f32;
+                ui.add(egui::ProgressBar::new(progress).show_percentage());
+                return;
+            }
+            ui.heading("Could not load the game");
+            ui.label("These files are missing or broken:");
+            for path in &preloaded.failed {
+                ui.colored_label(egui::Color32::RED, format!("assets/{}", path));
+            }
+            ui.label("Reinstalling the game should bring them back.");
+        });
+    });
+}
@@ -6,11 +6,18 @@ use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
 mod comm;
 mod constants;
+mod entry_detail;
 mod game_state;
 mod leaderboard;
+mod loading;
+mod my_runs;
 mod painting;
+mod pause;
 mod results;
+mod settings;
 mod start_menu;
+mod storage;
+mod textures;
 
 use crate::game_state::GameState;
 
@@ -26,21 +33,21 @@ fn main() {
         .add_state(GameState::Loading)
         .add_plugins(DefaultPlugins)
         .add_plugin(EguiPlugin)
+        .init_resource::<crate::textures::TextureRegistry>()
         .add_startup_system(setup)
+        .add_plugin(crate::settings::SettingsPlugin)
         .add_plugin(crate::start_menu::StartMenuPlugin)
         .add_plugin(crate::painting::PaintingPlugin)
+        .add_plugin(crate::pause::PausePlugin)
         .add_plugin(crate::results::ResultsPlugin)
         .add_plugin(crate::comm::CommPlugin)
         .add_plugin(crate::leaderboard::LeaderboardPlugin)
-        .add_startup_system(finish_loading)
+        .add_plugin(crate::entry_detail::EntryDetailPlugin)
+        .add_plugin(crate::my_runs::MyRunsPlugin)
+        .add_plugin(crate::loading::LoadingPlugin)
         .run();
 }
 
 fn setup(mut commands: Commands) {
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
 }
-
-// stub function to move out of loading state
-fn finish_loading(mut state: ResMut<State<GameState>>) {
-    state.set(GameState::StartMenu).unwrap();
-}
@@ -0,0 +1,405 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Line, Plot, Points, Value, Values},
+    },
+    EguiContext,
+};
+use serde::{Deserialize, Serialize};
+use unfair_coloring_competition::{
+    backend::{now_micros, CommError, NewEntry},
+    nickname::NicknameRules,
+    scoring::{
+        self, BrushShape, Level, Replay, ScoreBreakdown, BRUSH_IMAGE_SIZE, CANVAS_HEIGHT,
+        CANVAS_WIDTH, LEVELS,
+    },
+};
+
+use crate::{
+    comm::{Comm, Request, RequestId},
+    entry_detail::{format_timestamp, new_image, TargetImages},
+    game_state::GameState,
+    painting::{Run, Score},
+    settings::Settings,
+    storage,
+    textures::{TextureKey, TextureRegistry},
+};
+
+/// Storage key of the run history
+const RUNS_KEY: &str = "runs";
+/// Runs kept, the oldest are dropped first. Replays make records large and localStorage is
+/// small. My Runs tells the player about the limit.
+const MAX_RUNS: usize = 30;
+const MICROS_PER_DAY: f64 = 24. * 60. * 60. * 1_000_000.;
+
+pub struct MyRunsPlugin;
+impl Plugin for MyRunsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RunHistory::default())
+            .insert_resource(MyRunsView::default())
+            .add_system_set(SystemSet::on_enter(GameState::Loading).with_system(load_history))
+            .add_system_set(SystemSet::on_enter(GameState::Results).with_system(record_run))
+            .add_system_set(SystemSet::on_enter(GameState::MyRuns).with_system(register_brushes))
+            .add_system_set(
+                SystemSet::on_update(GameState::MyRuns)
+                    .with_system(egui_ui)
+                    .with_system(receive_submission),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::MyRuns).with_system(close));
+    }
+}
+
+/// A finished run. Brush and canvas aren't stored, they are painted again from the seed and
+/// the replay, which take far less space.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// microseconds since the unix epoch, also identifies the run
+    pub ts: i64,
+    pub level: String,
+    pub mode: String,
+    pub challenge: Option<String>,
+    /// whether the run may be submitted
+    pub ranked: bool,
+    pub seed: u32,
+    pub score: f64,
+    /// `None` if the target image wasn't loaded yet when the run finished
+    pub breakdown: Option<ScoreBreakdown>,
+    pub replay: Replay,
+    /// seconds spent painting
+    pub duration: f32,
+    /// seconds left when a timed run ended
+    pub remaining_time: Option<f32>,
+    /// version of the game the run was played with
+    pub version: String,
+    /// sent to the online leaderboard
+    pub submitted: bool,
+}
+
+impl RunRecord {
+    fn shape(&self) -> BrushShape {
+        BrushShape::from_seed(self.seed)
+    }
+
+    fn to_new_entry(&self, name: String) -> NewEntry {
+        let shape = self.shape();
+        NewEntry {
+            name,
+            score: Some(self.score),
+            brush: Some(base64::encode(scoring::render_brush(&shape))),
+            shape: serde_json::to_string(&shape).ok(),
+            drawing: None,
+            seed: Some(self.seed as i64),
+            replay: serde_json::to_string(&self.replay).ok(),
+            verified: None,
+            challenge: self.challenge.clone(),
+            level: Some(self.level.clone()),
+            mode: Some(self.mode.clone()),
+            version: Some(self.version.clone()),
+            duration: Some(self.duration as f64),
+            hidden: None,
+            remaining_time: self.remaining_time.map(f64::from),
+        }
+    }
+}
+
+/// Every finished run on this device, oldest first
+#[derive(Default)]
+pub struct RunHistory {
+    runs: Vec<RunRecord>,
+}
+
+impl RunHistory {
+    fn push(&mut self, record: RunRecord) {
+        self.runs.push(record);
+        let excess = self.runs.len().saturating_sub(MAX_RUNS);
+        self.runs.drain(..excess);
+        self.save();
+    }
+
+    fn get(&self, ts: i64) -> Option<&RunRecord> {
+        self.runs.iter().find(|run| run.ts == ts)
+    }
+
+    pub fn mark_submitted(&mut self, ts: i64) {
+        if let Some(run) = self.runs.iter_mut().find(|run| run.ts == ts) {
+            run.submitted = true;
+            self.save();
+        }
+    }
+
+    /// `ts` of the run that finished last, the one the results screen shows
+    pub fn last_run(&self) -> Option<i64> {
+        self.runs.last().map(|run| run.ts)
+    }
+
+    /// Marks the run that just finished as sent, for the results screen
+    pub fn mark_last_submitted(&mut self) {
+        if let Some(ts) = self.last_run() {
+            self.mark_submitted(ts);
+        }
+    }
+
+    fn save(&self) {
+        storage::save(RUNS_KEY, &self.runs);
+    }
+}
+
+struct MyRunsView {
+    /// level the chart shows
+    level: &'static Level,
+    /// run whose canvas is open
+    selected: Option<i64>,
+    /// run being submitted
+    submitting: Option<(i64, RequestId)>,
+    error: Option<CommError>,
+}
+
+impl Default for MyRunsView {
+    fn default() -> Self {
+        MyRunsView {
+            level: &LEVELS[0],
+            selected: None,
+            submitting: None,
+            error: None,
+        }
+    }
+}
+
+fn load_history(mut history: ResMut<RunHistory>) {
+    history.runs = storage::load(RUNS_KEY).unwrap_or_default();
+}
+
+fn record_run(
+    run: Res<Run>,
+    score: Res<Score>,
+    targets: Res<TargetImages>,
+    images: Res<Assets<Image>>,
+    mut history: ResMut<RunHistory>,
+) {
+    let breakdown = targets
+        .of(run.level)
+        .and_then(|target| images.get(target))
+        .map(|target| ScoreBreakdown::calculate(&target.data, &run.replay.simulate(&run.shape)));
+    history.push(RunRecord {
+        ts: now_micros(),
+        level: run.level.id.to_string(),
+        mode: run.mode.as_str().to_string(),
+        challenge: run.challenge.clone(),
+        ranked: run.ranked,
+        seed: run.seed,
+        score: score.0,
+        breakdown,
+        replay: run.replay.clone(),
+        duration: run.duration,
+        remaining_time: run.remaining_time(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        submitted: false,
+    });
+}
+
+fn register_brushes(
+    history: Res<RunHistory>,
+    mut images: ResMut<Assets<Image>>,
+    mut textures: ResMut<TextureRegistry>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    for run in &history.runs {
+        let brush = scoring::render_brush(&run.shape());
+        let handle = images.add(new_image(BRUSH_IMAGE_SIZE, BRUSH_IMAGE_SIZE, brush));
+        textures.register(TextureKey::HistoryBrush(run.ts), handle, &mut egui_ctx);
+    }
+}
+
+fn receive_submission(
+    mut comm: ResMut<Comm>,
+    mut view: ResMut<MyRunsView>,
+    mut history: ResMut<RunHistory>,
+) {
+    let (ts, id) = match view.submitting {
+        Some(submitting) => submitting,
+        None => return,
+    };
+    if let Some(result) = comm.take_submit(id) {
+        view.submitting = None;
+        match result {
+            Ok(_) => history.mark_submitted(ts),
+            Err(e) => view.error = Some(e),
+        }
+    }
+}
+
+/// Score of every run on `level`, days since the first of them against score
+fn score_over_time(history: &RunHistory, level: &Level) -> Vec<Value> {
+    let runs: Vec<&RunRecord> = history
+        .runs
+        .iter()
+        .filter(|run| run.level == level.id)
+        .collect();
+    let first = runs.first().map(|run| run.ts).unwrap_or_default();
+    runs.iter()
+        .map(|run| Value::new((run.ts - first) as f64 / MICROS_PER_DAY, run.score))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn egui_ui(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut view: ResMut<MyRunsView>,
+    history: Res<RunHistory>,
+    settings: Res<Settings>,
+    nickname_rules: Res<NicknameRules>,
+    mut comm: ResMut<Comm>,
+    mut textures: ResMut<TextureRegistry>,
+    mut images: ResMut<Assets<Image>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let view = &mut *view;
+    let nickname = nickname_rules.sanitize(&settings.nickname);
+    let mut back = false;
+    let mut submit = None;
+    let mut select = None;
+
+    egui::TopBottomPanel::top("my_runs_top").show(egui_ctx.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("Back to Start Menu").clicked() {
+                back = true;
+            }
+            egui::ComboBox::from_id_source("my_runs_level")
+                .selected_text(view.level.id)
+                .show_ui(ui, |ui| {
+                    for level in LEVELS {
+                        ui.selectable_value(&mut view.level, level, level.id);
+                    }
+                });
+        });
+        match &nickname {
+            Ok(name) => ui.label(format!("Runs are submitted as {}", name)),
+            Err(_) => ui.label("Send a result once to pick the nickname runs are submitted as"),
+        };
+        if let Some(e) = &view.error {
+            ui.colored_label(egui::Color32::RED, e.to_string());
+        }
+    });
+
+    egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        if history.runs.is_empty() {
+            ui.label("No runs yet");
+            return;
+        }
+
+        if history.runs.len() >= MAX_RUNS {
+            ui.label(format!(
+                "Only your last {} runs are kept, older ones are removed.",
+                MAX_RUNS
+            ));
+        }
+        let points = score_over_time(&history, view.level);
+        ui.label("Score by days since your first run on this level");
+        Plot::new("score_over_time")
+            .view_aspect(2.5)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(Values::from_values(points.clone())).name("score"));
+                plot_ui.points(Points::new(Values::from_values(points)).radius(3.));
+            });
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("my_runs").striped(true).show(ui, |ui| {
+                for run in history.runs.iter().rev() {
+                    match textures.get(&TextureKey::HistoryBrush(run.ts)) {
+                        Some(brush) => ui.image(brush, [40., 40.]),
+                        None => ui.label(""),
+                    };
+                    ui.label(format_timestamp(run.ts));
+                    ui.label(format!("{} {}", run.level, run.mode));
+                    ui.label(format!("{:.1}", run.score));
+                    match &run.breakdown {
+                        Some(breakdown) => ui.label(format!(
+                            "{} of {} px in, {} px out",
+                            breakdown.good, breakdown.max, breakdown.bad
+                        )),
+                        None => ui.label(""),
+                    };
+                    if ui
+                        .selectable_label(view.selected == Some(run.ts), "View")
+                        .clicked()
+                    {
+                        select = Some(run.ts);
+                    }
+                    if run.submitted {
+                        ui.label("sent");
+                    } else if !run.ranked {
+                        ui.label("practice");
+                    } else if view.submitting.map(|(ts, _)| ts) == Some(run.ts) {
+                        ui.label("Sending...");
+                    } else if ui
+                        .add_enabled(
+                            nickname.is_ok() && view.submitting.is_none(),
+                            egui::Button::new("Submit"),
+                        )
+                        .clicked()
+                    {
+                        submit = Some(run.ts);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    });
+
+    let mut open = true;
+    if let Some(ts) = view.selected {
+        egui::Window::new("Run")
+            .open(&mut open)
+            .collapsible(false)
+            .show(egui_ctx.ctx_mut(), |ui| {
+                if let Some(canvas) = textures.get(&TextureKey::HistoryCanvas(ts)) {
+                    ui.image(canvas, [300., 300.]);
+                }
+            });
+    }
+
+    if back {
+        // fails if a state change is already queued, the click is dropped then
+        let _ = state.set(GameState::StartMenu);
+    }
+    if let (Some(ts), Ok(name)) = (submit, nickname) {
+        if let Some(run) = history.get(ts) {
+            let id = comm.send(Request::Submit(run.to_new_entry(name)));
+            view.submitting = Some((ts, id));
+            view.error = None;
+        }
+    }
+    if !open || select.is_some() {
+        if let Some(ts) = view.selected.take() {
+            textures.unregister(&TextureKey::HistoryCanvas(ts), &mut egui_ctx);
+        }
+    }
+    if let Some(run) = select.and_then(|ts| history.get(ts)) {
+        let canvas = run.replay.simulate(&run.shape());
+        let handle = images.add(new_image(CANVAS_WIDTH, CANVAS_HEIGHT, canvas));
+        textures.register(TextureKey::HistoryCanvas(run.ts), handle, &mut egui_ctx);
+        view.selected = Some(run.ts);
+    }
+}
+
+fn close(
+    history: Res<RunHistory>,
+    mut view: ResMut<MyRunsView>,
+    mut comm: ResMut<Comm>,
+    mut textures: ResMut<TextureRegistry>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    for run in &history.runs {
+        textures.unregister(&TextureKey::HistoryBrush(run.ts), &mut egui_ctx);
+    }
+    if let Some(ts) = view.selected.take() {
+        textures.unregister(&TextureKey::HistoryCanvas(ts), &mut egui_ctx);
+    }
+    // leaving cancels a submission that hasn't come back yet
+    if let Some((_, id)) = view.submitting.take() {
+        comm.cancel(id);
+    }
+}
@@ -0,0 +1,162 @@
+//! Nickname rules, checked by the game before sending a result and again by the server.
+
+use std::fmt;
+
+/// Shortest accepted nickname, in characters after trimming
+pub const MIN_LEN: usize = 2;
+/// Longest accepted nickname, in characters after trimming
+pub const MAX_LEN: usize = 24;
+/// Punctuation allowed besides letters, digits and single spaces
+const ALLOWED_PUNCTUATION: &[char] = &['-', '_', '.', '\''];
+/// Words rejected by default, one per line
+const DEFAULT_BLOCKLIST: &str = include_str!("nickname_blocklist.txt");
+
+/// Why a nickname was rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NicknameError {
+    Empty,
+    TooShort,
+    TooLong,
+    InvalidChar(char),
+    Blocked,
+}
+
+impl fmt::Display for NicknameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NicknameError::Empty => write!(f, "Enter a nickname"),
+            NicknameError::TooShort => {
+                write!(f, "Nickname must be at least {} characters", MIN_LEN)
+            }
+            NicknameError::TooLong => write!(f, "Nickname must be at most {} characters", MAX_LEN),
+            NicknameError::InvalidChar(c) if c.is_control() => {
+                write!(f, "Nickname can't contain control characters")
+            }
+            NicknameError::InvalidChar(c) => write!(f, "Nickname can't contain '{}'", c),
+            NicknameError::Blocked => write!(f, "Please pick another nickname"),
+        }
+    }
+}
+
+impl std::error::Error for NicknameError {}
+
+/// Length, charset and blocklist for nicknames
+#[derive(Clone, Debug)]
+pub struct NicknameRules {
+    /// lowercase words without spaces or punctuation
+    blocklist: Vec<String>,
+}
+
+impl Default for NicknameRules {
+    fn default() -> Self {
+        Self::with_blocklist(DEFAULT_BLOCKLIST)
+    }
+}
+
+impl NicknameRules {
+    /// Rules blocking the words of `text`, one per line. Empty lines and lines starting with
+    /// `#` are skipped.
+    pub fn with_blocklist(text: &str) -> Self {
+        let blocklist = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(normalize)
+            .filter(|word| !word.is_empty())
+            .collect();
+        NicknameRules { blocklist }
+    }
+
+    /// The nickname as it should be stored: trimmed, with runs of whitespace turned into one
+    /// space. Fails if that isn't an acceptable nickname.
+    pub fn sanitize(&self, name: &str) -> Result<String, NicknameError> {
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Err(NicknameError::Empty);
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| !(c.is_alphanumeric() || *c == ' ' || ALLOWED_PUNCTUATION.contains(c)))
+        {
+            return Err(NicknameError::InvalidChar(c));
+        }
+        let len = name.chars().count();
+        if len < MIN_LEN {
+            return Err(NicknameError::TooShort);
+        }
+        if len > MAX_LEN {
+            return Err(NicknameError::TooLong);
+        }
+        if words(&name).any(|word| self.blocklist.contains(&word)) {
+            return Err(NicknameError::Blocked);
+        }
+        Ok(name)
+    }
+}
+
+/// The normalized words of a nickname, which is what the blocklist is matched against.
+/// Words are never matched inside longer ones or across words, or "Scunthorpe" and "Ad Min"
+/// would be blocked.
+fn words(name: &str) -> impl Iterator<Item = String> + '_ {
+    name.split(|c: char| !c.is_alphanumeric())
+        .map(normalize)
+        .filter(|token| !token.is_empty())
+}
+
+/// Lowercase letters and digits only
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str) -> Result<String, NicknameError> {
+        NicknameRules::default().sanitize(name)
+    }
+
+    #[test]
+    fn blocked_words_are_rejected() {
+        for name in ["admin", "The Admin", "ADMIN_42", "admin-x", "MODERATOR"] {
+            assert_eq!(check(name), Err(NicknameError::Blocked), "{}", name);
+        }
+    }
+
+    #[test]
+    fn words_inside_other_words_are_fine() {
+        for name in ["Scunthorpe", "Badminton", "Cockburn Bassett", "shiitake"] {
+            assert_eq!(check(name), Ok(name.to_string()));
+        }
+    }
+
+    #[test]
+    fn words_are_not_joined() {
+        for name in ["Ad Min", "Mod Erator", "A D M I N"] {
+            assert_eq!(check(name), Ok(name.to_string()));
+        }
+    }
+
+    #[test]
+    fn whitespace_is_collapsed() {
+        assert_eq!(
+            check("  Bob   the  painter "),
+            Ok("Bob the painter".to_string())
+        );
+    }
+
+    #[test]
+    fn length_and_characters_are_checked() {
+        assert_eq!(check("   "), Err(NicknameError::Empty));
+        assert_eq!(check("x"), Err(NicknameError::TooShort));
+        assert_eq!(check(&"x".repeat(MAX_LEN + 1)), Err(NicknameError::TooLong));
+        assert_eq!(check("bob<script>"), Err(NicknameError::InvalidChar('<')));
+        assert_eq!(
+            check("O'Neil-Smith_2.0"),
+            Ok("O'Neil-Smith_2.0".to_string())
+        );
+    }
+}
@@ -4,13 +4,18 @@ use bevy::{
 };
 use bevy_prototype_lyon::{prelude::*, shapes};
 use rand::{thread_rng, Rng};
+use unfair_coloring_competition::scoring::{
+    self, BrushShape, Level, Replay, ScoreBreakdown, BRUSH_IMAGE_SIZE, CANVAS_HEIGHT, CANVAS_WIDTH,
+    LEVELS, MAX_SEED, TIMED_BOARD,
+};
 
-use crate::game_state::GameState;
+use crate::{constants::FONT, game_state::GameState};
 
 pub struct PaintingPlugin;
 impl Plugin for PaintingPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(ShapePlugin)
+            .insert_resource(RunConfig::default())
             .add_system_set(
                 SystemSet::on_enter(GameState::Painting)
                     .with_system(setup_brush)
@@ -22,9 +27,12 @@ impl Plugin for PaintingPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::Painting)
                     .with_system(track_cursor.label("track_cursor"))
-                    .with_system(paint.after("track_cursor"))
+                    .with_system(paint.label("paint").after("track_cursor"))
                     .with_system(handle_done_clicked)
-                    .with_system(calculate_score),
+                    .with_system(track_duration.label("track_duration"))
+                    .with_system(count_down.after("track_duration"))
+                    // after painting so the final score includes the last frame's paint
+                    .with_system(calculate_score.after("paint")),
             )
             .add_system_set(
                 SystemSet::on_exit(GameState::Painting)
@@ -34,40 +42,113 @@ impl Plugin for PaintingPlugin {
     }
 }
 
-const CANVAS_WIDTH: usize = 600;
-const CANVAS_HEIGHT: usize = 600;
-
 #[derive(Component)]
 struct PaintingScene;
 
 #[derive(Component)]
-struct Paintbrush {
-    extents: Vec2,
+struct BrushParent;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    Free,
+    Daily,
+    /// ends when the level's time limit runs out
+    Timed,
 }
 
-#[derive(Component)]
-struct BrushParent;
+impl GameMode {
+    /// Value of the `mode` field of entries
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameMode::Free => "free",
+            GameMode::Daily => "daily",
+            GameMode::Timed => "timed",
+        }
+    }
+}
+
+/// How the next run is set up, picked on the start menu
+pub struct RunConfig {
+    pub mode: GameMode,
+    /// `None` for a random brush
+    pub seed: Option<u32>,
+    pub level: &'static Level,
+    /// daily challenge the run counts for
+    pub challenge: Option<String>,
+    /// whether the result may be submitted
+    pub ranked: bool,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            mode: GameMode::Free,
+            seed: None,
+            level: &LEVELS[0],
+            challenge: None,
+            ranked: true,
+        }
+    }
+}
+
+impl RunConfig {
+    /// A run against the clock on `level`
+    pub fn timed(level: &'static Level) -> Self {
+        RunConfig {
+            mode: GameMode::Timed,
+            level,
+            challenge: Some(TIMED_BOARD.to_string()),
+            ..RunConfig::default()
+        }
+    }
+
+    /// Sets up another run like `run`, with its brush or a new one. A daily challenge only
+    /// counts once, so playing it again is practice.
+    pub fn play_again(&mut self, run: &Run, same_brush: bool) {
+        self.seed = same_brush.then_some(run.seed);
+        if self.mode == GameMode::Daily {
+            self.ranked = false;
+        }
+    }
+}
+
+/// The brush of the current run and everything painted with it
+pub struct Run {
+    pub seed: u32,
+    pub shape: BrushShape,
+    pub replay: Replay,
+    pub challenge: Option<String>,
+    pub ranked: bool,
+    pub mode: GameMode,
+    pub level: &'static Level,
+    /// seconds spent painting
+    pub duration: f32,
+    /// seconds the run may last, `None` for untimed runs
+    pub time_limit: Option<f32>,
+}
 
-const BRUSH_RECT_MIN: f32 = 20.;
-const BRUSH_RECT_MAX: f32 = 100.;
-const BRUSH_MAX_OFFSET: f32 = 75.;
+impl Run {
+    pub fn remaining_time(&self) -> Option<f32> {
+        self.time_limit
+            .map(|limit| (limit - self.duration).max(0.0))
+    }
+}
 
-fn setup_brush(mut commands: Commands) {
+fn setup_brush(mut commands: Commands, config: Res<RunConfig>) {
     commands.insert_resource(PaintReady(false));
-    let mut rng = thread_rng();
+    let seed = config
+        .seed
+        .unwrap_or_else(|| thread_rng().gen_range(0..=MAX_SEED));
+    let shape = BrushShape::from_seed(seed);
     let parent_id = commands
         .spawn()
         .insert(BrushParent)
         .insert(Transform::default())
         .insert(GlobalTransform::default())
         .id();
-    for _ in 0..3 {
-        let width = rng.gen_range(BRUSH_RECT_MIN..BRUSH_RECT_MAX);
-        let height = rng.gen_range(BRUSH_RECT_MIN..BRUSH_RECT_MAX);
-        let extents = Vec2::new(width, height);
-
-        let offset_x = rng.gen_range(-BRUSH_MAX_OFFSET..BRUSH_MAX_OFFSET);
-        let offset_y = rng.gen_range(-BRUSH_MAX_OFFSET..BRUSH_MAX_OFFSET);
+    for rect in &shape.rects {
+        let extents = Vec2::from(rect.extents);
+        let [offset_x, offset_y] = rect.offset;
 
         commands
             .entity(parent_id)
@@ -81,11 +162,22 @@ fn setup_brush(mut commands: Commands) {
                         DrawMode::Fill(FillMode::color(Color::rgb_u8(200, 140, 50))),
                         Transform::from_xyz(offset_x, offset_y, 0.0),
                     ))
-                    .insert(Paintbrush { extents })
                     .insert(PaintingScene);
             })
             .insert(PaintingScene);
     }
+
+    commands.insert_resource(Run {
+        seed,
+        shape,
+        replay: Replay::default(),
+        challenge: config.challenge.clone(),
+        ranked: config.ranked,
+        mode: config.mode,
+        level: config.level,
+        duration: 0.0,
+        time_limit: (config.mode == GameMode::Timed).then(|| config.level.time_limit as f32),
+    });
 }
 
 fn track_cursor(
@@ -95,7 +187,7 @@ fn track_cursor(
 ) {
     let window = windows.get_primary().unwrap();
     for position in cursor_pos.iter() {
-        let size = Vec2::new(window.width() as f32, window.height() as f32);
+        let size = Vec2::new(window.width(), window.height());
         let mut t = brush.single_mut();
         t.translation = (position.position - size / 2.0).extend(2.0);
     }
@@ -162,7 +254,7 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: Text::with_section(
                         "Done",
                         TextStyle {
-                            font: asset_server.load("fonts/Archivo-Black.ttf"),
+                            font: asset_server.load(FONT),
                             font_size: 40.0,
                             color: Color::rgb(0.9, 0.9, 0.9),
                         },
@@ -180,8 +272,8 @@ fn handle_done_clicked(
     mut mouse_button: ResMut<Input<MouseButton>>,
 ) {
     for interaction in interaction_query.iter_mut() {
-        if *interaction == Interaction::Clicked {
-            state.set(GameState::Results).unwrap();
+        // count_down may have ended the run in the same frame
+        if *interaction == Interaction::Clicked && state.set(GameState::Results).is_ok() {
             mouse_button.clear();
         }
     }
@@ -190,10 +282,14 @@ fn handle_done_clicked(
 #[derive(Component)]
 struct TargetImage;
 
-fn setup_target_image(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_target_image(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<RunConfig>,
+) {
     commands
         .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("images/Unfair_Duck-01.png"),
+            texture: asset_server.load(config.level.target),
             ..SpriteBundle::default()
         })
         .insert(TargetImage)
@@ -207,8 +303,9 @@ fn paint(
     q: Query<&Handle<Image>, With<PaintingArea>>,
     mut images: ResMut<Assets<Image>>,
     mouse_button: Res<Input<MouseButton>>,
-    brush: Query<(&mut GlobalTransform, &Paintbrush)>,
+    brush: Query<&Transform, With<BrushParent>>,
     mut ready: ResMut<PaintReady>,
+    mut run: ResMut<Run>,
 ) {
     if !ready.0 {
         if mouse_button.just_released(MouseButton::Left) {
@@ -217,45 +314,47 @@ fn paint(
         return;
     }
     if mouse_button.pressed(MouseButton::Left) {
-        for (t, Paintbrush { extents }) in brush.iter() {
-            let cursor_pos = get_canvas_position_from_translation(t);
-            let brush_top_left = cursor_pos - *extents / 2.0;
-            let handle = q.single();
-            let image = images.get_mut(handle).unwrap();
+        let position = brush.single().translation.truncate().to_array();
+        let image = images.get_mut(q.single()).unwrap();
 
-            paint_rect(extents, brush_top_left, image);
-        }
+        // painting goes through the same code the server replays
+        scoring::paint_brush(&mut image.data, &run.shape, position);
+        run.replay.record(position);
     }
 }
 
-fn paint_rect(extents: &Vec2, top_left: Vec2, image: &mut Image) {
-    for x in 0..(extents.x as u32) {
-        for y in 0..(extents.y as u32) {
-            let pos = top_left + Vec2::new(x as f32, y as f32);
-            color_pixel(image, pos);
-        }
-    }
-}
-
-fn get_start_byte(x: usize, y: usize) -> usize {
-    (y * CANVAS_WIDTH as usize + x) * 4
+fn track_duration(time: Res<Time>, mut run: ResMut<Run>) {
+    run.duration += time.delta_seconds();
 }
 
-fn get_canvas_position_from_translation(t: &GlobalTransform) -> Vec2 {
-    let mut canvas_pos =
-        t.translation.truncate() + Vec2::new(CANVAS_WIDTH as f32, -(CANVAS_HEIGHT as f32)) / 2.0;
-    canvas_pos.y = -canvas_pos.y;
-    canvas_pos
+/// Ends a timed run once its time is up
+fn count_down(
+    run: Res<Run>,
+    mut timer_text: Query<&mut Text, With<TimerText>>,
+    mut state: ResMut<State<GameState>>,
+    mut mouse_button: ResMut<Input<MouseButton>>,
+) {
+    let remaining = match run.remaining_time() {
+        Some(remaining) => remaining,
+        None => return,
+    };
+    for mut text in timer_text.iter_mut() {
+        text.sections[0].value = format_countdown(remaining);
+        if remaining < 10.0 {
+            text.sections[0].style.color = Color::RED;
+        }
+    }
+    if remaining <= 0.0 {
+        // the done button may have ended the run this frame already
+        if state.set(GameState::Results).is_ok() {
+            mouse_button.clear();
+        }
+    }
 }
 
-fn color_pixel(image: &mut Image, pos: Vec2) {
-    if pos.x < 0.0 || pos.y < 0.0 || pos.x >= CANVAS_WIDTH as f32 || pos.y >= CANVAS_HEIGHT as f32 {
-        return;
-    }
-    let start_byte = get_start_byte(pos.x as usize, pos.y as usize);
-    let new = [255, 0, 0, 255];
-    let splice_range = start_byte..(start_byte + 4);
-    image.data.splice(splice_range, new);
+fn format_countdown(seconds: f32) -> String {
+    let seconds = seconds.ceil() as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
 }
 
 #[derive(Default)]
@@ -264,15 +363,45 @@ pub struct Score(pub f64);
 #[derive(Component)]
 pub struct ScoreText;
 
-fn setup_score(mut commands: Commands, asset_server: Res<AssetServer>) {
+#[derive(Component)]
+struct TimerText;
+
+fn setup_score(mut commands: Commands, asset_server: Res<AssetServer>, config: Res<RunConfig>) {
     commands.insert_resource(Score(0.0));
 
+    if config.mode == GameMode::Timed {
+        commands
+            .spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format_countdown(config.level.time_limit as f32),
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(5.0),
+                        right: Val::Px(5.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(TimerText)
+            .insert(PaintingScene);
+    }
+
     commands
         .spawn_bundle(TextBundle {
             text: Text::with_section(
                 format!("Score: {:.1}", 0.0),
                 TextStyle {
-                    font: asset_server.load("fonts/Archivo-Black.ttf"),
+                    font: asset_server.load(FONT),
                     font_size: 40.0,
                     color: Color::WHITE,
                 },
@@ -301,37 +430,11 @@ fn calculate_score(
     mut score_text: Query<&mut Text, With<ScoreText>>,
     mut last_score: Local<Score>,
 ) {
-    let target_image = images.get(target_image.single());
-    if target_image.is_none() {
-        return;
-    }
-    let target_image = target_image.unwrap();
+    // targets are loaded before the start menu, see `loading`
+    let target_image = images.get(target_image.single()).unwrap();
     let player_image = images.get(player_image.single()).unwrap();
 
-    let mut sum_good = 0;
-    let mut sum_bad = 0;
-    let mut max_score = 0;
-    for x in 0..CANVAS_WIDTH {
-        for y in 0..CANVAS_HEIGHT {
-            let start_byte = get_start_byte(x, y);
-            // use if not white
-            let should_color = target_image.data[start_byte] != 255;
-            if should_color {
-                max_score += 1;
-            }
-            // use alpha transparency
-            let is_colored = player_image.data[start_byte + 3] == 255;
-            if is_colored {
-                if should_color {
-                    sum_good += 1;
-                } else {
-                    sum_bad += 1;
-                }
-            }
-        }
-    }
-
-    score.0 = ((sum_good - sum_bad) as f64 / max_score as f64) * 100.0;
+    score.0 = ScoreBreakdown::calculate(&target_image.data, &player_image.data).score();
     let mut score_text = score_text.single_mut();
     score_text.sections[0].value = format!("Score: {:.1}", score.0);
 
@@ -354,36 +457,19 @@ pub struct PaintbrushImageHandle(pub Handle<Image>);
 fn generate_paintbrush_texture(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
-    brush: Query<(&mut Transform, &Paintbrush)>,
+    run: Res<Run>,
 ) {
-    let d = BRUSH_RECT_MAX + 2. * BRUSH_MAX_OFFSET;
-    let mut image = Image::new_fill(
+    let image = Image::new(
         Extent3d {
-            width: d as u32,
-            height: d as u32,
+            width: BRUSH_IMAGE_SIZE as u32,
+            height: BRUSH_IMAGE_SIZE as u32,
             ..Default::default()
         },
         TextureDimension::D2,
-        &[255, 255, 255, 255],
+        scoring::render_brush(&run.shape),
         TextureFormat::Rgba8Unorm,
     );
 
-    for (t, Paintbrush { extents }) in brush.iter() {
-        let mut canvas_pos = t.translation.truncate() + Vec2::new(d, -d) / 2.0;
-        canvas_pos.y = -canvas_pos.y;
-        let brush_top_left = canvas_pos - *extents / 2.0;
-        for x in 0..(extents.x as u32) {
-            for y in 0..(extents.y as u32) {
-                let pos = brush_top_left + Vec2::new(x as f32, y as f32);
-
-                let start_byte = (pos.y as usize * d as usize + pos.x as usize) * 4;
-                let new = [255, 0, 0, 255];
-                let splice_range = start_byte..(start_byte + 4);
-                image.data.splice(splice_range, new);
-            }
-        }
-    }
-
     let handle = images.add(image);
 
     commands.insert_resource(PaintbrushImageHandle(handle));
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game_state::GameState,
+    painting::{GameMode, Run, RunConfig},
+};
+
+/// Escape during a run pushes `GameState::Paused` on top of `GameState::Painting`. Painting's
+/// update systems don't run while it isn't the active state, so painting and the run timer
+/// are frozen until the pause is popped again.
+pub struct PausePlugin;
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameState::Painting).with_system(pause))
+            .add_system_set(SystemSet::on_update(GameState::Paused).with_system(egui_ui));
+    }
+}
+
+fn pause(mut keyboard: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    // fails if the run ends in the same frame, then there is nothing to pause
+    if keyboard.just_pressed(KeyCode::Escape) && state.push(GameState::Paused).is_ok() {
+        keyboard.clear();
+    }
+}
+
+enum PauseAction {
+    Resume,
+    Restart { same_brush: bool },
+    Quit,
+}
+
+fn egui_ui(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse_button: ResMut<Input<MouseButton>>,
+    mut state: ResMut<State<GameState>>,
+    mut config: ResMut<RunConfig>,
+    run: Res<Run>,
+) {
+    let mut action = None;
+    if keyboard.just_pressed(KeyCode::Escape) {
+        action = Some(PauseAction::Resume);
+        keyboard.clear();
+    }
+    let daily = config.mode == GameMode::Daily;
+
+    egui::Window::new("Paused")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.vertical_centered_justified(|ui| {
+                if ui.button("Resume").clicked() {
+                    action = Some(PauseAction::Resume);
+                }
+                if ui.button("Restart with the same brush").clicked() {
+                    action = Some(PauseAction::Restart { same_brush: true });
+                }
+                // the daily brush is the challenge
+                if ui
+                    .add_enabled(!daily, egui::Button::new("Restart with a new brush"))
+                    .clicked()
+                {
+                    action = Some(PauseAction::Restart { same_brush: false });
+                }
+                if ui.button("Quit to Menu").clicked() {
+                    action = Some(PauseAction::Quit);
+                }
+            });
+            if daily && config.ranked {
+                ui.label("Restarting makes today's challenge a practice run.");
+            }
+        });
+
+    let changed = match action {
+        Some(PauseAction::Resume) => state.pop().is_ok(),
+        Some(PauseAction::Restart { same_brush }) => {
+            // replacing the whole stack exits the paused run, so it is set up from scratch
+            let restarted = state.replace(GameState::Painting).is_ok();
+            if restarted {
+                config.play_again(&run, same_brush);
+            }
+            restarted
+        }
+        Some(PauseAction::Quit) => state.replace(GameState::StartMenu).is_ok(),
+        None => false,
+    };
+    if changed {
+        // the click on the pause menu must not paint
+        mouse_button.clear();
+    }
+}
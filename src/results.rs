@@ -2,98 +2,297 @@ use bevy::prelude::*;
 use bevy_egui::{egui, EguiContext};
 
 use crate::{
-    comm::{create_drawings::DrawingsInput, CommChannels},
+    comm::{Comm, Request, RequestId, SubmissionQueue},
     game_state::GameState,
-    painting::{PaintbrushImageHandle, Score},
+    leaderboard::{Board, OwnEntry},
+    my_runs::RunHistory,
+    painting::{GameMode, PaintbrushImageHandle, Run, RunConfig, Score},
+    settings::Settings,
+    textures::{TextureKey, TextureRegistry},
+};
+use unfair_coloring_competition::{
+    backend::{CommError, NewEntry},
+    nickname::NicknameRules,
 };
 
 pub struct ResultsPlugin;
 impl Plugin for ResultsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(GameState::Results).with_system(set_egui_image))
-            .add_system_set(
-                SystemSet::on_update(GameState::Results)
-                    .with_system(egui_ui)
-                    .with_system(check_done),
-            )
-            .insert_resource(UserNick::default())
-            .insert_resource(ResultCommStatus::Waiting);
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Results)
+                .with_system(set_egui_image)
+                .with_system(reset_submission)
+                .with_system(prefill_nickname)
+                .with_system(record_personal_best),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Results)
+                // the bottom panel has to be laid out before the central one
+                .with_system(navigation_ui.before("results_ui"))
+                .with_system(egui_ui.label("results_ui"))
+                .with_system(check_done),
+        )
+        .insert_resource(UserNick::default())
+        .insert_resource(NicknameRules::default())
+        .insert_resource(SentEntry(None))
+        .insert_resource(NewPersonalBest(false))
+        .insert_resource(ResultCommStatus::Waiting);
     }
 }
 
-#[derive(Component)]
-struct SendButton;
-
 #[derive(Default)]
 struct UserNick(pub String);
 
+/// Whether the run beat the player's best on its level
+struct NewPersonalBest(bool);
+
+/// The entry of the last "Send Result", kept to queue it if the leaderboard can't be reached
+struct SentEntry(Option<NewEntry>);
+
 #[derive(PartialEq, Eq, Clone)]
 enum ResultCommStatus {
     Waiting,
-    Sending,
+    Sending(RequestId),
     Done,
-    Error(String),
+    /// saved to the submission queue to be sent later
+    Queued,
+    Error(CommError),
 }
 
 #[allow(clippy::too_many_arguments)]
 fn egui_ui(
     mut egui_ctx: ResMut<EguiContext>,
     mut user_nick: ResMut<UserNick>,
-    comm_channels: ResMut<CommChannels>,
+    mut comm: ResMut<Comm>,
     mut result_comm_status: ResMut<ResultCommStatus>,
     score: Res<Score>,
+    run: Res<Run>,
     paint_brush_handle: Res<PaintbrushImageHandle>,
     images: Res<Assets<Image>>,
+    mut sent_entry: ResMut<SentEntry>,
+    textures: Res<TextureRegistry>,
+    nickname_rules: Res<NicknameRules>,
+    mut settings: ResMut<Settings>,
+    new_best: Res<NewPersonalBest>,
 ) {
     egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        let nickname = nickname_rules.sanitize(&user_nick.0);
         ui.horizontal(|ui| {
             ui.label("Nickname: ");
             ui.text_edit_singleline(&mut user_nick.0);
 
-            if ui.add(egui::Button::new("Send Result")).clicked() {
+            let sending = matches!(*result_comm_status, ResultCommStatus::Sending(_));
+            // a queued result is sent in the background, sending it here too would post it twice
+            let sent = matches!(
+                *result_comm_status,
+                ResultCommStatus::Queued | ResultCommStatus::Done
+            );
+            let enabled = !sending && !sent && run.ranked && nickname.is_ok();
+            if ui
+                .add_enabled(enabled, egui::Button::new("Send Result"))
+                .clicked()
+            {
                 let image = images.get(paint_brush_handle.0.clone()).unwrap();
                 let image_hex = base64::encode(&image.data);
-                comm_channels
-                    .result_req_tx
-                    .try_send(DrawingsInput {
-                        name: user_nick.0.clone(),
-                        score: Some(score.0),
-                        brush: Some(image_hex),
-                        shape: None,
-                        drawing: None,
-                    })
-                    .unwrap();
-                *result_comm_status = ResultCommStatus::Sending;
+                let entry = NewEntry {
+                    name: nickname.clone().unwrap_or_default(),
+                    score: Some(score.0),
+                    brush: Some(image_hex),
+                    shape: serde_json::to_string(&run.shape).ok(),
+                    drawing: None,
+                    seed: Some(run.seed as i64),
+                    replay: serde_json::to_string(&run.replay).ok(),
+                    verified: None,
+                    challenge: run.challenge.clone(),
+                    level: Some(run.level.id.to_string()),
+                    mode: Some(run.mode.as_str().to_string()),
+                    version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    duration: Some(run.duration as f64),
+                    hidden: None,
+                    remaining_time: run.remaining_time().map(f64::from),
+                };
+                settings.set_nickname(&entry.name);
+                let id = comm.send(Request::Submit(entry.clone()));
+                sent_entry.0 = Some(entry);
+                *result_comm_status = ResultCommStatus::Sending(id);
             };
+            if sending {
+                ui.label(format!("{} Sending", spinner(ui)));
+            }
         });
 
+        if new_best.0 {
+            ui.label("New personal best!");
+        }
+
+        // an empty field is the starting state, not a mistake
+        if let Err(e) = &nickname {
+            if !user_nick.0.is_empty() {
+                ui.colored_label(egui::Color32::RED, e.to_string());
+            }
+        }
+
+        if !run.ranked {
+            ui.label("You already played today's challenge, practice runs can't be submitted.");
+        }
+
         if let ResultCommStatus::Error(e) = result_comm_status.clone() {
             ui.horizontal(|ui| {
-                ui.label(e);
+                ui.colored_label(egui::Color32::RED, e.to_string());
+                if let Some(entry) = sent_entry.0.clone() {
+                    // with the nickname as it is now, it may have been changed after the error
+                    if ui
+                        .add_enabled(nickname.is_ok(), egui::Button::new("Retry"))
+                        .clicked()
+                    {
+                        let entry = NewEntry {
+                            name: nickname.clone().unwrap_or_default(),
+                            ..entry
+                        };
+                        settings.set_nickname(&entry.name);
+                        let id = comm.send(Request::Submit(entry.clone()));
+                        sent_entry.0 = Some(entry);
+                        *result_comm_status = ResultCommStatus::Sending(id);
+                    }
+                }
             });
         }
+        if *result_comm_status == ResultCommStatus::Queued {
+            ui.label(
+                "Could not reach the leaderboard. Your result was saved and will be sent \
+                 automatically once you are back online.",
+            );
+        }
 
-        ui.vertical_centered(|ui| {
-            ui.image(egui::TextureId::User(0), [50., 50.]);
+        if let Some(brush) = textures.get(&TextureKey::RunBrush) {
+            ui.vertical_centered(|ui| {
+                ui.image(brush, [50., 50.]);
+            });
+        }
+    });
+}
+
+/// A rotating bar, redrawn every frame while something is pending
+fn spinner(ui: &egui::Ui) -> char {
+    const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+    ui.ctx().request_repaint();
+    FRAMES[(ui.input().time * 8.) as usize % FRAMES.len()]
+}
+
+/// Ways off the results screen besides sending the result
+fn navigation_ui(
+    mut egui_ctx: ResMut<EguiContext>,
+    result_comm_status: Res<ResultCommStatus>,
+    run: Res<Run>,
+    mut config: ResMut<RunConfig>,
+    mut board: ResMut<Board>,
+    mut state: ResMut<State<GameState>>,
+    mut mouse_button: ResMut<Input<MouseButton>>,
+) {
+    // leaving while sending would drop the response
+    let sending = matches!(*result_comm_status, ResultCommStatus::Sending(_));
+    let mut next = None;
+    egui::TopBottomPanel::bottom("results_navigation").show(egui_ctx.ctx_mut(), |ui| {
+        ui.add_enabled_ui(!sending, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Play Again").clicked() {
+                    config.play_again(&run, true);
+                    next = Some(GameState::Painting);
+                }
+                // the daily brush is the challenge
+                if ui
+                    .add_enabled(
+                        config.mode != GameMode::Daily,
+                        egui::Button::new("Play Again with a new brush"),
+                    )
+                    .clicked()
+                {
+                    config.play_again(&run, false);
+                    next = Some(GameState::Painting);
+                }
+                if ui.button("Skip to Leaderboard").clicked() {
+                    board.0 = run.challenge.clone();
+                    next = Some(GameState::LeaderBoard);
+                }
+                if ui.button("Back to Menu").clicked() {
+                    next = Some(GameState::StartMenu);
+                }
+            });
         });
     });
+    // fails if another state change was queued in the same frame
+    if let Some(next) = next {
+        if state.set(next).is_ok() {
+            mouse_button.clear();
+        }
+    }
+}
+
+fn reset_submission(
+    mut result_comm_status: ResMut<ResultCommStatus>,
+    mut sent_entry: ResMut<SentEntry>,
+) {
+    *result_comm_status = ResultCommStatus::Waiting;
+    sent_entry.0 = None;
 }
 
+fn prefill_nickname(mut user_nick: ResMut<UserNick>, settings: Res<Settings>) {
+    if user_nick.0.is_empty() {
+        user_nick.0 = settings.nickname.clone();
+    }
+}
+
+fn record_personal_best(
+    score: Res<Score>,
+    run: Res<Run>,
+    mut settings: ResMut<Settings>,
+    mut new_best: ResMut<NewPersonalBest>,
+) {
+    new_best.0 = settings.record_score(run.level, score.0);
+}
+
+#[allow(clippy::too_many_arguments)]
 fn check_done(
-    comm_channels: ResMut<CommChannels>,
+    mut comm: ResMut<Comm>,
     mut result_comm_status: ResMut<ResultCommStatus>,
     mut state: ResMut<State<GameState>>,
+    mut sent_entry: ResMut<SentEntry>,
+    mut queue: ResMut<SubmissionQueue>,
+    mut own_entry: ResMut<OwnEntry>,
+    mut board: ResMut<Board>,
+    mut history: ResMut<RunHistory>,
 ) {
-    if let Ok(res) = comm_channels.result_res_rx.try_recv() {
-        if let Err(e) = res {
-            *result_comm_status = ResultCommStatus::Error(e);
-        } else {
-            *result_comm_status = ResultCommStatus::Done;
-            state.set(GameState::LeaderBoard).unwrap();
+    let id = match *result_comm_status {
+        ResultCommStatus::Sending(id) => id,
+        _ => return,
+    };
+    if let Some(res) = comm.take_submit(id) {
+        match res {
+            Err(e) if e.is_transient() => match sent_entry.0.take() {
+                Some(entry) => {
+                    queue.push(entry, history.last_run());
+                    *result_comm_status = ResultCommStatus::Queued;
+                }
+                None => *result_comm_status = ResultCommStatus::Error(e),
+            },
+            Err(e) => *result_comm_status = ResultCommStatus::Error(e),
+            Ok(entry) => {
+                own_entry.set(entry.id);
+                history.mark_last_submitted();
+                // show the board the entry went to
+                board.0 = sent_entry.0.take().and_then(|sent| sent.challenge);
+                *result_comm_status = ResultCommStatus::Done;
+                // if another state change got there first the result screen shows it was sent
+                let _ = state.set(GameState::LeaderBoard);
+            }
         }
     }
 }
 
-fn set_egui_image(handle: Res<PaintbrushImageHandle>, mut egui_context: ResMut<EguiContext>) {
-    egui_context.set_egui_texture(0, handle.0.clone());
+fn set_egui_image(
+    handle: Res<PaintbrushImageHandle>,
+    mut textures: ResMut<TextureRegistry>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    textures.register(TextureKey::RunBrush, handle.0.clone(), &mut egui_context);
 }
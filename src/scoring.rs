@@ -0,0 +1,229 @@
+//! Brush generation, painting and scoring rules.
+//!
+//! The game and the server both use these, so the server can replay a run and get the exact
+//! score the player saw.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+pub const CANVAS_WIDTH: usize = 600;
+pub const CANVAS_HEIGHT: usize = 600;
+
+pub const BRUSH_RECT_MIN: f32 = 20.;
+pub const BRUSH_RECT_MAX: f32 = 100.;
+pub const BRUSH_MAX_OFFSET: f32 = 75.;
+const BRUSH_RECT_COUNT: usize = 3;
+/// width and height of the image a brush is rendered to
+pub const BRUSH_IMAGE_SIZE: usize = (BRUSH_RECT_MAX + 2. * BRUSH_MAX_OFFSET) as usize;
+
+/// Seeds are kept in the positive range of a graphql `Int`
+pub const MAX_SEED: u32 = i32::MAX as u32;
+
+/// Most positions a run records per second, one a frame on a fast display
+pub const MAX_REPLAY_RATE: f64 = 240.0;
+/// Most positions replayed, ten minutes of painting at 60 fps. Every position paints three
+/// rects of up to 100px square, so this bounds the work a single replay costs.
+pub const MAX_REPLAY_POINTS: usize = 10 * 60 * 60;
+
+pub const TARGET_IMAGE: &str = "images/Unfair_Duck-01.png";
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Level {
+    /// stored in the `level` field of entries
+    pub id: &'static str,
+    pub target: &'static str,
+    /// seconds a timed run on this level lasts
+    pub time_limit: u32,
+}
+
+/// Levels a run can be played on. Entries from before levels existed have no `level` and were
+/// painted on the first one. The server only verifies against `TARGET_IMAGE`, so add new
+/// targets there too.
+pub const LEVELS: &[Level] = &[Level {
+    id: "duck",
+    target: TARGET_IMAGE,
+    time_limit: 60,
+}];
+
+/// Value of the `challenge` field of timed runs, which compete on a board of their own
+pub const TIMED_BOARD: &str = "timed";
+
+impl Level {
+    pub fn from_id(id: &str) -> Option<&'static Level> {
+        LEVELS.iter().find(|level| level.id == id)
+    }
+}
+
+const BLANK_PIXEL: [u8; 4] = [255, 255, 255, 0];
+const PAINT_PIXEL: [u8; 4] = [255, 0, 0, 255];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BrushRect {
+    pub extents: [f32; 2],
+    /// offset of the rect's center from the cursor
+    pub offset: [f32; 2],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BrushShape {
+    pub rects: Vec<BrushRect>,
+}
+
+impl BrushShape {
+    /// Generates the brush for `seed`. Uses chacha so the same seed gives the same brush on
+    /// every platform.
+    pub fn from_seed(seed: u32) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+        let rects = (0..BRUSH_RECT_COUNT)
+            .map(|_| {
+                let width = rng.gen_range(BRUSH_RECT_MIN..BRUSH_RECT_MAX);
+                let height = rng.gen_range(BRUSH_RECT_MIN..BRUSH_RECT_MAX);
+
+                let offset_x = rng.gen_range(-BRUSH_MAX_OFFSET..BRUSH_MAX_OFFSET);
+                let offset_y = rng.gen_range(-BRUSH_MAX_OFFSET..BRUSH_MAX_OFFSET);
+
+                BrushRect {
+                    extents: [width, height],
+                    offset: [offset_x, offset_y],
+                }
+            })
+            .collect();
+        BrushShape { rects }
+    }
+}
+
+/// Cursor positions the brush was painted at, in order.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Replay(pub Vec<[f32; 2]>);
+
+impl Replay {
+    pub fn record(&mut self, position: [f32; 2]) {
+        // painting is idempotent, so holding the brush still doesn't need more points
+        if self.0.last() != Some(&position) {
+            self.0.push(position);
+        }
+    }
+
+    /// Most positions a run painting for `duration` seconds can have recorded, the game records
+    /// at most one a frame. Runs without a duration, from older clients, get the overall limit.
+    pub fn max_points(duration: Option<f64>) -> usize {
+        match duration {
+            Some(duration) if duration.is_finite() => {
+                let frames = (duration.max(0.0) * MAX_REPLAY_RATE).ceil() as usize + 1;
+                frames.min(MAX_REPLAY_POINTS)
+            }
+            _ => MAX_REPLAY_POINTS,
+        }
+    }
+
+    /// The positions without repeats, in the order they were first painted at. Every position
+    /// paints the same color, so the canvas doesn't change.
+    pub fn distinct(&self) -> Replay {
+        let mut seen = std::collections::HashSet::new();
+        Replay(
+            self.0
+                .iter()
+                .copied()
+                .filter(|[x, y]| seen.insert((x.to_bits(), y.to_bits())))
+                .collect(),
+        )
+    }
+
+    /// Paints the replay onto a blank canvas
+    pub fn simulate(&self, shape: &BrushShape) -> Vec<u8> {
+        let mut canvas = blank_canvas();
+        for position in &self.0 {
+            paint_brush(&mut canvas, shape, *position);
+        }
+        canvas
+    }
+}
+
+/// rgba bytes of an unpainted canvas
+pub fn blank_canvas() -> Vec<u8> {
+    BLANK_PIXEL.repeat(CANVAS_WIDTH * CANVAS_HEIGHT)
+}
+
+/// Paints `shape` onto the canvas with the cursor at `position`.
+/// `position` is in world coordinates, with the origin at the center of the canvas and y up.
+pub fn paint_brush(canvas: &mut [u8], shape: &BrushShape, position: [f32; 2]) {
+    for rect in &shape.rects {
+        let x = position[0] + rect.offset[0] + CANVAS_WIDTH as f32 / 2.0;
+        let y = -(position[1] + rect.offset[1] - CANVAS_HEIGHT as f32 / 2.0);
+        let top_left = [x - rect.extents[0] / 2.0, y - rect.extents[1] / 2.0];
+        paint_rect(canvas, CANVAS_WIDTH, CANVAS_HEIGHT, rect.extents, top_left);
+    }
+}
+
+/// rgba bytes of the brush drawn on white, `BRUSH_IMAGE_SIZE` pixels square
+pub fn render_brush(shape: &BrushShape) -> Vec<u8> {
+    let d = BRUSH_IMAGE_SIZE;
+    let mut image = [255u8; 4].repeat(d * d);
+    for rect in &shape.rects {
+        let x = rect.offset[0] + d as f32 / 2.0;
+        let y = -(rect.offset[1] - d as f32 / 2.0);
+        let top_left = [x - rect.extents[0] / 2.0, y - rect.extents[1] / 2.0];
+        paint_rect(&mut image, d, d, rect.extents, top_left);
+    }
+    image
+}
+
+fn paint_rect(
+    image: &mut [u8],
+    width: usize,
+    height: usize,
+    extents: [f32; 2],
+    top_left: [f32; 2],
+) {
+    for x in 0..(extents[0] as u32) {
+        for y in 0..(extents[1] as u32) {
+            let pos_x = top_left[0] + x as f32;
+            let pos_y = top_left[1] + y as f32;
+            if pos_x < 0.0 || pos_y < 0.0 || pos_x >= width as f32 || pos_y >= height as f32 {
+                continue;
+            }
+            let start_byte = (pos_y as usize * width + pos_x as usize) * 4;
+            image[start_byte..start_byte + 4].copy_from_slice(&PAINT_PIXEL);
+        }
+    }
+}
+
+/// Pixel counts a score is computed from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    /// painted pixels inside the target
+    pub good: u32,
+    /// painted pixels outside the target
+    pub bad: u32,
+    /// pixels in the target
+    pub max: u32,
+}
+
+impl ScoreBreakdown {
+    /// Compares the rgba bytes of the target and the painted canvas
+    pub fn calculate(target: &[u8], canvas: &[u8]) -> Self {
+        let mut breakdown = ScoreBreakdown::default();
+        for (target, painted) in target.chunks_exact(4).zip(canvas.chunks_exact(4)) {
+            // use if not white
+            let should_color = target[0] != 255;
+            if should_color {
+                breakdown.max += 1;
+            }
+            // use alpha transparency
+            let is_colored = painted[3] == 255;
+            if is_colored {
+                if should_color {
+                    breakdown.good += 1;
+                } else {
+                    breakdown.bad += 1;
+                }
+            }
+        }
+        breakdown
+    }
+
+    pub fn score(&self) -> f64 {
+        ((self.good as f64 - self.bad as f64) / self.max as f64) * 100.0
+    }
+}
@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use bevy_egui::EguiSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use unfair_coloring_competition::scoring::{Level, LEVELS};
+
+use crate::{game_state::GameState, storage};
+
+/// Storage key of the settings, kept in the OS config dir
+const SETTINGS_KEY: &str = "settings";
+/// UI scales the settings window offers
+pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f64> = 0.75..=2.0;
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::default())
+            .add_system_set(SystemSet::on_enter(GameState::Loading).with_system(load_settings))
+            .add_system(apply_ui_scale);
+    }
+}
+
+/// What the player chose and achieved, remembered between sessions.
+/// Changed through the methods, which save right away.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// nickname of the last sent result
+    pub nickname: String,
+    /// level id the start button plays
+    pub level: String,
+    /// whether the start button plays against the clock
+    pub timed: bool,
+    /// scale of the egui windows, for readability
+    pub ui_scale: f64,
+    /// best score per level id
+    pub personal_bests: BTreeMap<String, f64>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            nickname: String::new(),
+            level: LEVELS[0].id.to_string(),
+            timed: false,
+            ui_scale: 1.0,
+            personal_bests: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn save(&self) {
+        storage::save_config(SETTINGS_KEY, self);
+    }
+
+    /// The preferred level, the first one if it no longer exists
+    pub fn level(&self) -> &'static Level {
+        Level::from_id(&self.level).unwrap_or(&LEVELS[0])
+    }
+
+    pub fn set_nickname(&mut self, nickname: &str) {
+        if self.nickname != nickname {
+            self.nickname = nickname.to_string();
+            self.save();
+        }
+    }
+
+    pub fn personal_best(&self, level: &Level) -> Option<f64> {
+        self.personal_bests.get(level.id).copied()
+    }
+
+    /// Keeps `score` if it beats the best on `level`, returns whether it did
+    pub fn record_score(&mut self, level: &Level, score: f64) -> bool {
+        if self.personal_best(level).is_some_and(|best| best >= score) {
+            return false;
+        }
+        self.personal_bests.insert(level.id.to_string(), score);
+        self.save();
+        true
+    }
+}
+
+fn load_settings(mut settings: ResMut<Settings>) {
+    if let Some(stored) = storage::load_config(SETTINGS_KEY) {
+        *settings = stored;
+    }
+}
+
+fn apply_ui_scale(settings: Res<Settings>, mut egui_settings: ResMut<EguiSettings>) {
+    // a hand edited config file may hold anything
+    let scale = settings
+        .ui_scale
+        .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end());
+    if settings.is_changed() && egui_settings.scale_factor != scale {
+        egui_settings.scale_factor = scale;
+    }
+}
@@ -1,23 +1,40 @@
 use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use unfair_coloring_competition::{daily::DailyChallenge, scoring::LEVELS};
 
 use crate::{
-    constants::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    comm::SubmissionQueue,
+    constants::{FONT, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON, SPLASH_IMAGE},
     game_state::GameState,
+    painting::{GameMode, RunConfig},
+    settings::{Settings, UI_SCALE_RANGE},
+    storage,
 };
 
+/// Storage key of the last day a daily challenge was started
+const DAILY_ATTEMPT_KEY: &str = "daily_attempt";
+
 pub struct StartMenuPlugin;
 impl Plugin for StartMenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_enter(GameState::StartMenu)
                 .with_system(setup_button)
-                .with_system(setup_splash_image),
+                .with_system(setup_daily_button)
+                .with_system(setup_my_runs_button)
+                .with_system(setup_splash_image)
+                .with_system(setup_queue_status),
         )
         .add_system(button_hover_system)
         .add_system_set(
             SystemSet::on_update(GameState::StartMenu)
                 .with_system(handle_start_clicked)
-                .with_system(handle_leaderboard_clicked),
+                .with_system(handle_daily_clicked)
+                .with_system(handle_leaderboard_clicked)
+                .with_system(handle_my_runs_clicked)
+                .with_system(update_queue_status)
+                .with_system(dropped_submissions_ui)
+                .with_system(settings_ui),
         )
         .add_system_set(SystemSet::on_exit(GameState::StartMenu).with_system(despawn_button));
     }
@@ -29,9 +46,18 @@ struct StartMenuScene;
 #[derive(Component)]
 struct StartButton;
 
+#[derive(Component)]
+struct DailyButton;
+
 #[derive(Component)]
 struct LeaderboardButton;
 
+#[derive(Component)]
+struct MyRunsButton;
+
+#[derive(Component)]
+struct QueueStatusText;
+
 fn setup_button(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn_bundle(UiCameraBundle::default());
 
@@ -63,7 +89,7 @@ fn setup_button(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: Text::with_section(
                         "Start",
                         TextStyle {
-                            font: asset_server.load("fonts/Archivo-Black.ttf"),
+                            font: asset_server.load(FONT),
                             font_size: 40.0,
                             color: Color::rgb(0.9, 0.9, 0.9),
                         },
@@ -102,7 +128,7 @@ fn setup_button(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: Text::with_section(
                         "Leaderboard",
                         TextStyle {
-                            font: asset_server.load("fonts/Archivo-Black.ttf"),
+                            font: asset_server.load(FONT),
                             font_size: 40.0,
                             color: Color::rgb(0.9, 0.9, 0.9),
                         },
@@ -114,15 +140,222 @@ fn setup_button(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
 }
 
+/// Only the first daily run of a day is ranked, later ones are practice
+fn played_daily_today() -> bool {
+    storage::load::<i64>(DAILY_ATTEMPT_KEY) == Some(DailyChallenge::today().day)
+}
+
+fn setup_daily_button(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let label = if played_daily_today() {
+        "Daily (practice)"
+    } else {
+        "Daily"
+    };
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(220.0), Val::Px(50.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    right: Val::Px(5.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .insert(DailyButton)
+        .insert(StartMenuScene)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        label,
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: 25.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(StartMenuScene);
+        });
+}
+
+fn setup_my_runs_button(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(220.0), Val::Px(50.0)),
+                position_type: PositionType::Absolute,
+                // below the daily button
+                position: Rect {
+                    top: Val::Px(60.0),
+                    right: Val::Px(5.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .insert(MyRunsButton)
+        .insert(StartMenuScene)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "My Runs",
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: 25.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(StartMenuScene);
+        });
+}
+
 fn setup_splash_image(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands
         .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("images/Unfair_Start_Screen.png"),
+            texture: asset_server.load(SPLASH_IMAGE),
             ..SpriteBundle::default()
         })
         .insert(StartMenuScene);
 }
 
+fn setup_queue_status(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT),
+                    font_size: 20.0,
+                    color: Color::rgb(0.15, 0.15, 0.15),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(QueueStatusText)
+        .insert(StartMenuScene);
+}
+
+fn update_queue_status(
+    queue: Res<SubmissionQueue>,
+    mut text: Query<&mut Text, With<QueueStatusText>>,
+) {
+    let count = queue.entries.len();
+    let status = if count == 0 {
+        String::new()
+    } else if queue.is_sending() {
+        format!("Sending {} saved result(s)...", count)
+    } else {
+        format!("{} saved result(s) waiting to be sent", count)
+    };
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+/// Tells the player about queued results the leaderboard refused
+fn dropped_submissions_ui(mut egui_ctx: ResMut<EguiContext>, mut queue: ResMut<SubmissionQueue>) {
+    if queue.dropped.is_empty() {
+        return;
+    }
+    let mut dismissed = false;
+    egui::Window::new("Results not sent")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label("The leaderboard refused these saved results:");
+            for dropped in &queue.dropped {
+                ui.label(format!(
+                    "{} ({:.1}): {}",
+                    dropped.entry.name,
+                    dropped.entry.score.unwrap_or_default(),
+                    dropped.reason
+                ));
+            }
+            ui.label("You can send them again from My Runs.");
+            dismissed = ui.button("OK").clicked();
+        });
+    if dismissed {
+        queue.dismiss_dropped();
+    }
+}
+
+fn settings_ui(mut egui_ctx: ResMut<EguiContext>, mut settings: ResMut<Settings>) {
+    // changed on a copy so `settings` is only marked changed when something was edited
+    let mut edited = settings.clone();
+    egui::Window::new("Settings")
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_BOTTOM, [5., -5.])
+        .show(egui_ctx.ctx_mut(), |ui| {
+            egui::Grid::new("settings").show(ui, |ui| {
+                ui.label("Level");
+                egui::ComboBox::from_id_source("settings_level")
+                    .selected_text(edited.level().id)
+                    .show_ui(ui, |ui| {
+                        for level in LEVELS {
+                            ui.selectable_value(&mut edited.level, level.id.to_string(), level.id);
+                        }
+                    });
+                ui.end_row();
+                ui.label("Time limit");
+                let limit = edited.level().time_limit;
+                ui.checkbox(&mut edited.timed, format!("{} s", limit));
+                ui.end_row();
+                ui.label("UI scale");
+                ui.add(egui::Slider::new(&mut edited.ui_scale, UI_SCALE_RANGE));
+                ui.end_row();
+            });
+            if !edited.personal_bests.is_empty() {
+                ui.separator();
+                ui.label("Personal bests");
+                egui::Grid::new("personal_bests").show(ui, |ui| {
+                    for (level, best) in &edited.personal_bests {
+                        ui.label(level);
+                        ui.label(format!("{:.1}", best));
+                        ui.end_row();
+                    }
+                });
+            }
+        });
+    if edited.level != settings.level
+        || edited.timed != settings.timed
+        || edited.ui_scale != settings.ui_scale
+    {
+        *settings = edited;
+        settings.save();
+    }
+}
+
 fn despawn_button(mut commands: Commands, query: Query<Entity, With<StartMenuScene>>) {
     for e in query.iter() {
         commands.entity(e).despawn();
@@ -151,13 +384,48 @@ fn button_hover_system(
 }
 
 fn handle_start_clicked(
+    mut config: ResMut<RunConfig>,
+    settings: Res<Settings>,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<StartButton>)>,
     mut state: ResMut<State<GameState>>,
     mut mouse_button: ResMut<Input<MouseButton>>,
 ) {
     for interaction in interaction_query.iter_mut() {
-        if *interaction == Interaction::Clicked {
-            state.set(GameState::Painting).unwrap();
+        // fails if another button queued a state change in the same frame
+        if *interaction == Interaction::Clicked && state.set(GameState::Painting).is_ok() {
+            *config = if settings.timed {
+                RunConfig::timed(settings.level())
+            } else {
+                RunConfig {
+                    level: settings.level(),
+                    ..RunConfig::default()
+                }
+            };
+            mouse_button.clear();
+        }
+    }
+}
+
+fn handle_daily_clicked(
+    mut config: ResMut<RunConfig>,
+    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<DailyButton>)>,
+    mut state: ResMut<State<GameState>>,
+    mut mouse_button: ResMut<Input<MouseButton>>,
+) {
+    for interaction in interaction_query.iter_mut() {
+        // the config is only touched once the run is sure to start
+        if *interaction == Interaction::Clicked && state.set(GameState::Painting).is_ok() {
+            let daily = DailyChallenge::today();
+            let ranked = !played_daily_today();
+            // the attempt counts once it starts, so quitting a bad run doesn't give a retry
+            storage::save(DAILY_ATTEMPT_KEY, &daily.day);
+            *config = RunConfig {
+                mode: GameMode::Daily,
+                seed: Some(daily.seed),
+                level: daily.level,
+                challenge: Some(daily.id()),
+                ranked,
+            };
             mouse_button.clear();
         }
     }
@@ -169,8 +437,19 @@ fn handle_leaderboard_clicked(
     mut mouse_button: ResMut<Input<MouseButton>>,
 ) {
     for interaction in interaction_query.iter_mut() {
-        if *interaction == Interaction::Clicked {
-            state.set(GameState::LeaderBoard).unwrap();
+        if *interaction == Interaction::Clicked && state.set(GameState::LeaderBoard).is_ok() {
+            mouse_button.clear();
+        }
+    }
+}
+
+fn handle_my_runs_clicked(
+    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<MyRunsButton>)>,
+    mut state: ResMut<State<GameState>>,
+    mut mouse_button: ResMut<Input<MouseButton>>,
+) {
+    for interaction in interaction_query.iter_mut() {
+        if *interaction == Interaction::Clicked && state.set(GameState::MyRuns).is_ok() {
             mouse_button.clear();
         }
     }
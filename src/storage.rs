@@ -0,0 +1,97 @@
+//! Small json key-value store for data that should outlive the game.
+//! Natively every key is a file in the OS data or config dir, on wasm it's an entry in
+//! localStorage.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+const APP_NAME: &str = "unfair_coloring_competition";
+
+/// Where a key is kept natively. On wasm both live in localStorage.
+#[derive(Clone, Copy)]
+enum Location {
+    Data,
+    /// things the player chose, in the OS config dir
+    Config,
+}
+
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    load_from(Location::Data, key)
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    save_to(Location::Data, key, value)
+}
+
+pub fn load_config<T: DeserializeOwned>(key: &str) -> Option<T> {
+    load_from(Location::Config, key)
+}
+
+pub fn save_config<T: Serialize>(key: &str, value: &T) {
+    save_to(Location::Config, key, value)
+}
+
+fn load_from<T: DeserializeOwned>(location: Location, key: &str) -> Option<T> {
+    let text = read(location, key)?;
+    match serde_json::from_str(&text) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            bevy::log::warn!("ignoring stored {}: {}", key, e);
+            None
+        }
+    }
+}
+
+fn save_to<T: Serialize>(location: Location, key: &str, value: &T) {
+    let result = serde_json::to_string(value)
+        .map_err(|e| e.to_string())
+        .and_then(|text| write(location, key, &text));
+    if let Err(e) = result {
+        bevy::log::error!("could not store {}: {}", key, e);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(location: Location, key: &str) -> std::path::PathBuf {
+    let dir = match location {
+        Location::Data => dirs::data_dir(),
+        Location::Config => dirs::config_dir(),
+    };
+    dir.unwrap_or_default()
+        .join(APP_NAME)
+        .join(key)
+        .with_extension("json")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(location: Location, key: &str) -> Option<String> {
+    std::fs::read_to_string(path(location, key)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(location: Location, key: &str, text: &str) -> Result<(), String> {
+    let path = path(location, key);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(_location: Location, key: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("{}.{}", APP_NAME, key))
+        .ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(_location: Location, key: &str, text: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or_else(|| "localStorage is not available".to_string())?
+        .set_item(&format!("{}.{}", APP_NAME, key), text)
+        .map_err(|e| format!("{:?}", e))
+}
@@ -0,0 +1,64 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContext};
+
+/// What a texture shows. Leaderboard textures are keyed by document `_id`, so two entries
+/// with the same name never share one.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureKey {
+    /// brush of the run on the results screen
+    RunBrush,
+    /// leaderboard thumbnail
+    Brush(String),
+    /// the brush in the entry detail window
+    DetailBrush(String),
+    /// the painted canvas in the entry detail window
+    Drawing(String),
+    /// brush of a run in the local history, keyed by the run's timestamp
+    HistoryBrush(i64),
+    /// canvas of the run opened in the local history
+    HistoryCanvas(i64),
+}
+
+struct Registered {
+    egui_id: u64,
+    #[allow(dead_code)] // handle is saved here to prevent unload
+    handle: Handle<Image>,
+}
+
+/// Hands out egui texture ids. Ids are never reused, so a stale id can't show another
+/// entry's texture.
+#[derive(Default)]
+pub struct TextureRegistry {
+    textures: HashMap<TextureKey, Registered>,
+    next_id: u64,
+}
+
+impl TextureRegistry {
+    /// Makes `handle` drawable as `key`, replacing what was registered for it before
+    pub fn register(
+        &mut self,
+        key: TextureKey,
+        handle: Handle<Image>,
+        egui_ctx: &mut EguiContext,
+    ) -> u64 {
+        self.unregister(&key, egui_ctx);
+        let egui_id = self.next_id;
+        self.next_id += 1;
+        egui_ctx.set_egui_texture(egui_id, handle.clone());
+        self.textures.insert(key, Registered { egui_id, handle });
+        egui_id
+    }
+
+    pub fn get(&self, key: &TextureKey) -> Option<egui::TextureId> {
+        self.textures
+            .get(key)
+            .map(|registered| egui::TextureId::User(registered.egui_id))
+    }
+
+    /// Frees the texture, its image is unloaded once nothing else holds the handle
+    pub fn unregister(&mut self, key: &TextureKey, egui_ctx: &mut EguiContext) {
+        if let Some(registered) = self.textures.remove(key) {
+            egui_ctx.remove_egui_texture(registered.egui_id);
+        }
+    }
+}